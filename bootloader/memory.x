@@ -13,12 +13,16 @@ MEMORY
   /* Reasonable, unless you are doing something     */
   /* really particular with DMA or other concurrent */
   /* access that would benefit from striping        */
-  RAM   : ORIGIN = 0x20000000, LENGTH = 264K
+  RAM   : ORIGIN = 0x20000000, LENGTH = 264K - 256
+
+  /* The last 256 bytes hold the firmware crash record, which must survive the */
+  /* bootloader. Keep in sync with CRASH_RECORD in the firmware memory.x.      */
+  CRASH_RECORD : ORIGIN = 0x20041F00, LENGTH = 256
 
   /* OPTION B: Keep the unstriped sections separate */
   /* RAM: ORIGIN = 0x20000000, LENGTH = 256K        */
   /* SCRATCH_A: ORIGIN = 0x20040000, LENGTH = 4K    */
-  /* SCRATCH_B: ORIGIN = 0x20041000, LENGTH = 4K    */
+  /* SCRATCH_B: ORIGIN = 0x20041000, LENGTH = 4K - 256 */
 }
 
 __bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOT2);
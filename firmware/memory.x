@@ -5,7 +5,11 @@ MEMORY
   FLASH             : ORIGIN = 0x10007000, LENGTH = 512K
   DFU               : ORIGIN = 0x10087000, LENGTH = 516K
   APPDATA           : ORIGIN = 0x10108000, LENGTH = 64K
-  RAM               : ORIGIN = 0x20000000, LENGTH = 264K
+  EVENTLOG          : ORIGIN = 0x10118000, LENGTH = 128K
+  TELEMETRY         : ORIGIN = 0x10138000, LENGTH = 1024K
+  RAM               : ORIGIN = 0x20000000, LENGTH = 264K - 256
+  /* Crash record kept across resets, reserved in the bootloader as well */
+  CRASH_RECORD      : ORIGIN = 0x20041F00, LENGTH = 256
 }
 
 __bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOT2);
@@ -16,3 +20,12 @@ __bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOT2);
 
 __bootloader_appdata_start = ORIGIN(APPDATA) - ORIGIN(BOOT2);
 __bootloader_appdata_end = ORIGIN(APPDATA) + LENGTH(APPDATA) - ORIGIN(BOOT2);
+
+__eventlog_start = ORIGIN(EVENTLOG) - ORIGIN(BOOT2);
+__eventlog_end = ORIGIN(EVENTLOG) + LENGTH(EVENTLOG) - ORIGIN(BOOT2);
+
+__telemetry_start = ORIGIN(TELEMETRY) - ORIGIN(BOOT2);
+__telemetry_end = ORIGIN(TELEMETRY) + LENGTH(TELEMETRY) - ORIGIN(BOOT2);
+
+__crash_record_start = ORIGIN(CRASH_RECORD);
+__crash_record_end = ORIGIN(CRASH_RECORD) + LENGTH(CRASH_RECORD);
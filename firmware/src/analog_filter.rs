@@ -0,0 +1,142 @@
+// Filters applied to the analog input samples. Each channel has its own filter
+// type and window size, persisted in the config as a packed u16.
+
+/// Maximum filter window size in samples
+pub const MAX_FILTER_WINDOW: usize = 32;
+
+/// Filter types. The discriminant is part of the I2C API and the config format.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum FilterType {
+    /// Moving average over the last N samples
+    MovingAverage = 0,
+    /// Exponential moving average with alpha = 2 / (N + 1)
+    Ema = 1,
+    /// Median of the last N samples
+    Median = 2,
+    /// Average the last N ADC bursts before decimating to the sampling interval
+    Oversample = 3,
+}
+
+impl FilterType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::MovingAverage),
+            1 => Some(Self::Ema),
+            2 => Some(Self::Median),
+            3 => Some(Self::Oversample),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct FilterConfig {
+    pub filter_type: FilterType,
+    /// Window size in samples, 1..=MAX_FILTER_WINDOW
+    pub window: u8,
+}
+
+impl FilterConfig {
+    pub const fn new(filter_type: FilterType, window: u8) -> Self {
+        Self {
+            filter_type,
+            window,
+        }
+    }
+
+    /// Validate and build a filter config
+    pub fn from_parts(filter_type: u8, window: u8) -> Option<Self> {
+        let filter_type = FilterType::from_u8(filter_type)?;
+        if window == 0 || window as usize > MAX_FILTER_WINDOW {
+            return None;
+        }
+        Some(Self::new(filter_type, window))
+    }
+
+    /// Packed representation stored in the config: filter type in the high byte,
+    /// window size in the low byte
+    pub const fn to_u16(self) -> u16 {
+        ((self.filter_type as u16) << 8) | self.window as u16
+    }
+
+    pub fn from_u16(value: u16) -> Option<Self> {
+        Self::from_parts((value >> 8) as u8, value as u8)
+    }
+}
+
+pub struct Filter {
+    config: FilterConfig,
+    samples: [f32; MAX_FILTER_WINDOW],
+    index: usize,
+    sum: f32,
+    count: usize,
+    ema: f32,
+}
+
+impl Filter {
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            samples: [0.0; MAX_FILTER_WINDOW],
+            index: 0,
+            sum: 0.0,
+            count: 0,
+            ema: 0.0,
+        }
+    }
+
+    fn window(&self) -> usize {
+        self.config.window as usize
+    }
+
+    pub fn add_sample(&mut self, value: f32) {
+        let window = self.window();
+        if self.count < window {
+            self.samples[self.index] = value;
+            self.sum += value;
+            self.count += 1;
+        } else {
+            self.sum -= self.samples[self.index];
+            self.samples[self.index] = value;
+            self.sum += value;
+        }
+        self.index = (self.index + 1) % window;
+
+        if self.count == 1 {
+            self.ema = value;
+        } else {
+            let alpha = 2.0 / (window as f32 + 1.0);
+            self.ema += alpha * (value - self.ema);
+        }
+    }
+
+    pub fn value(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        match self.config.filter_type {
+            FilterType::MovingAverage => self.sum / self.count as f32,
+            FilterType::Ema => self.ema,
+            FilterType::Median => self.median(),
+            // Averaging is done on the ADC bursts; pass the latest sample through
+            FilterType::Oversample => {
+                let window = self.window();
+                self.samples[(self.index + window - 1) % window]
+            }
+        }
+    }
+
+    fn median(&self) -> f32 {
+        let mut sorted = [0.0f32; MAX_FILTER_WINDOW];
+        let sorted = &mut sorted[..self.count];
+        sorted.copy_from_slice(&self.samples[..self.count]);
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+        let mid = self.count / 2;
+        if self.count % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        }
+    }
+}
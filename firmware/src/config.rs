@@ -1,10 +1,14 @@
+use crate::analog_filter::{FilterConfig, FilterType};
+
 pub const I2C_ADDR: u8 = 0x6d; // I2C address for the device secondary interface
 
 pub const VSCAP_MAX_ALARM: f32 = 10.6; // V; Voltage should never exceed this value
 pub const VSCAP_MAX_VALUE: f32 = 11.0; // V; Maximum voltage for Vscap
 
 pub const DEFAULT_VSCAP_CORRECTION_SCALE: f32 = 1.059; // Default correction scale for Vscap
-pub const VSCAP_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x100a; // Key for Vscap correction scale in the config
+pub const VSCAP_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x100a; // Legacy key, migrated to VSCAP_CORRECTION_GAIN_CONFIG_KEY
+pub const VSCAP_CORRECTION_GAIN_CONFIG_KEY: u16 = 0x100f;
+pub const VSCAP_CORRECTION_OFFSET_CONFIG_KEY: u16 = 0x1010;
 
 // Default values for power thresholds
 pub const DEFAULT_VSCAP_POWER_ON_THRESHOLD: f32 = 8.0; // V
@@ -16,13 +20,82 @@ pub const DEFAULT_VIN_POWER_THRESHOLD: f32 = 9.0; // V
 pub const VIN_POWER_THRESHOLD_CONFIG_KEY: u16 = 0x1004;
 pub const VIN_MAX_VALUE: f32 = 40.0; // V
 pub const DEFAULT_VIN_CORRECTION_SCALE: f32 = 1.015; // Default correction scale for VIN
-pub const VIN_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x1008; // Key for VIN correction scale in the config
+pub const VIN_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x1008; // Legacy key, migrated to VIN_CORRECTION_GAIN_CONFIG_KEY
+pub const VIN_CORRECTION_GAIN_CONFIG_KEY: u16 = 0x100d;
+pub const VIN_CORRECTION_OFFSET_CONFIG_KEY: u16 = 0x100e;
+// Number of consecutive ADC bursts (about 0.3 ms each) with VIN below the power threshold
+// before a VIN loss is signalled to the state machine
+pub const VIN_FAST_DROP_BURSTS: u32 = 2;
 
 pub const IIN_MAX_VALUE: f32 = 3.3; // V; Maximum voltage for Iin
 // Default correction scale for Iin. The default value is experimentally determined to correct
 // scaling error present in the 0.4.0 hardware.
 pub const DEFAULT_IIN_CORRECTION_SCALE: f32 = 0.811_533_1;
-pub const IIN_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x1009;
+pub const IIN_CORRECTION_SCALE_CONFIG_KEY: u16 = 0x1009; // Legacy key, migrated to IIN_CORRECTION_GAIN_CONFIG_KEY
+pub const IIN_CORRECTION_GAIN_CONFIG_KEY: u16 = 0x1011;
+pub const IIN_CORRECTION_OFFSET_CONFIG_KEY: u16 = 0x1012;
+
+// Analog readings are computed as raw * ADC scale * correction scale + correction offset.
+// Offsets are in V for the voltage channels and in A for the current channel.
+pub const DEFAULT_CORRECTION_OFFSET: f32 = 0.0;
+
+// Analog input filters. The default matches the original 10-sample moving average.
+pub const DEFAULT_ANALOG_FILTER: FilterConfig = FilterConfig::new(FilterType::MovingAverage, 10);
+pub const VIN_FILTER_CONFIG_KEY: u16 = 0x1013;
+pub const VSCAP_FILTER_CONFIG_KEY: u16 = 0x1014;
+pub const IIN_FILTER_CONFIG_KEY: u16 = 0x1015;
+
+// User analog input on GPIO29_ADC3. The reading is the pin voltage (0..3.3 V)
+// multiplied by the scale plus the offset, in whatever unit the user chooses.
+pub const DEFAULT_ADC3_SCALE: f32 = 1.0;
+pub const ADC3_SCALE_CONFIG_KEY: u16 = 0x1016;
+pub const DEFAULT_ADC3_OFFSET: f32 = 0.0;
+pub const ADC3_OFFSET_CONFIG_KEY: u16 = 0x1017;
+
+// Header GPIO pins GPIO06-GPIO08. Configs are packed as mode (high byte) and flags (low byte);
+// the default is a floating input.
+pub const HEADER_GPIO_CONFIG_KEYS: [u16; 3] = [0x1018, 0x1019, 0x101a];
+pub const DEFAULT_HEADER_GPIO_CONFIG: u16 = 0x0000;
+pub const HEADER_GPIO_POLL_INTERVAL_MS: u32 = 5; // Input sampling interval for levels and edges
+
+// Host attention output, packed as pin (bits 0-7, 0xff = disabled), active high (bits 8-15)
+// and event mask (bits 16-31). Default: disabled, active low, all events.
+pub const ATTENTION_CONFIG_KEY: u16 = 0x101b;
+pub const DEFAULT_ATTENTION_CONFIG: u32 = 0xffff_00ff;
+pub const HOST_EVENT_QUEUE_DEPTH: usize = 32; // Oldest events are dropped when the queue is full
+pub const TRANSITION_HISTORY_DEPTH: usize = 32; // State transitions kept in RAM
+
+// Lifetime operating statistics, stored as a versioned 32-byte block
+pub const LIFETIME_STATS_KEY: u16 = 0x101c;
+pub const LIFETIME_STATS_VERSION: u8 = 1;
+pub const LIFETIME_STATS_UPDATE_INTERVAL_MS: u32 = 1000; // Uptime and energy integration interval
+pub const LIFETIME_STATS_CHECKPOINT_INTERVAL_MS: u32 = 15 * 60 * 1000; // Periodic flash checkpoint
+
+// Rolling telemetry min/max/mean windows
+pub const TELEMETRY_SAMPLE_INTERVAL_MS: u32 = 20; // Matches the analog input publishing interval
+pub const TELEMETRY_WARMUP_MS: u32 = 1000; // Skip samples while the analog filters settle at boot
+pub const TELEMETRY_MINUTE_BUCKETS: usize = 12; // 5 s buckets in the 1-minute window
+pub const TELEMETRY_HOUR_BUCKETS: usize = 60; // 1 min buckets in the 1-hour window
+
+// Long-term telemetry logger in the TELEMETRY flash partition
+pub const TELEMETRY_LOG_INTERVAL_CONFIG_KEY: u16 = 0x101d;
+pub const DEFAULT_TELEMETRY_LOG_INTERVAL_S: u16 = 60; // ~45 days of history in 1 MB
+pub const MIN_TELEMETRY_LOG_INTERVAL_S: u16 = 10; // Limits flash wear
+pub const TELEMETRY_LOG_CHUNK_RECORDS: usize = 8; // Records per I2C download chunk
+
+// Power-loss waveform capture around operational -> blackout transitions
+pub const WAVEFORM_SAMPLE_INTERVAL_US: u32 = 1000;
+pub const WAVEFORM_PRE_TRIGGER_SAMPLES: usize = 500; // 500 ms before the trigger
+pub const WAVEFORM_POST_TRIGGER_SAMPLES: usize = 2000; // 2 s after the trigger
+
+// Software RTC drift estimation from repeated host time sets
+pub const RTC_DRIFT_MIN_INTERVAL_MS: u32 = 60 * 60 * 1000; // Shorter intervals are too imprecise
+pub const RTC_MAX_DRIFT_PPM: i32 = 1000; // Larger estimates are discarded as host clock jumps
+
+// Weekly power-on/shutdown schedule, stored as SCHEDULE_SLOTS 4-byte slots
+pub const SCHEDULE_CONFIG_KEY: u16 = 0x101e;
+pub const SCHEDULE_SLOTS: usize = 8;
+pub const SCHEDULE_MAX_CATCH_UP_MINUTES: u32 = 5; // Larger clock steps skip the schedule
 
 // Time to wait for device to shut down gracefully.
 // Once this time is reached, the device will forcefully shut down.
@@ -41,6 +114,31 @@ pub const OFF_STATE_DURATION_MS: u32 = 5000; // ms
 pub const HOST_WATCHDOG_DEFAULT_TIMEOUT_MS: u16 = 10_000; // ms
 pub const HOST_WATCHDOG_TIMEOUT_CONFIG_KEY: u16 = 0x1006; // Key for the watchdog timeout in the config
 
+// Only explicit pings (register 0x1b) ping the host watchdog, not any I2C traffic
+pub const EXPLICIT_PING_ONLY_CONFIG_KEY: u16 = 0x101f;
+pub const DEFAULT_EXPLICIT_PING_ONLY: bool = false;
+
+// Boot watchdog: enter co-op mode as soon as the CM5 is on, with this deadline for the
+// first host ping (s, 0 = disabled). Later pings use the stored watchdog timeout.
+pub const BOOT_WATCHDOG_TIMEOUT_CONFIG_KEY: u16 = 0x1020;
+pub const DEFAULT_BOOT_WATCHDOG_TIMEOUT_S: u16 = 0;
+pub const MIN_BOOT_WATCHDOG_TIMEOUT_S: u16 = 30;
+
+// CM5 liveness detection in solo mode from the LED_ACTIVE and LED_PWR lines
+pub const LIVENESS_TIMEOUT_CONFIG_KEY: u16 = 0x1021; // Minutes without LED_ACTIVE activity (0 = disabled)
+pub const LIVENESS_RECOVERY_CONFIG_KEY: u16 = 0x1022; // Recovery action (see `LivenessRecovery`)
+pub const DEFAULT_LIVENESS_TIMEOUT_MIN: u16 = 0;
+pub const DEFAULT_LIVENESS_RECOVERY: u8 = 0;
+pub const LIVENESS_PWR_WINDOW_MS: u32 = 10_000; // Window for counting LED_PWR edges
+pub const LIVENESS_PWR_BLINK_EDGES: u8 = 6; // LED_PWR edges in a window that count as blinking
+
+// CM5 boot error code decoding from the LED_nACT line
+pub const BOOT_CODE_WINDOW_MS: u32 = 120_000; // How long to decode after entering SystemStartup
+pub const BOOT_CODE_SHORT_MIN_MS: u32 = 100; // Shorter flashes are disk activity, not a code
+pub const BOOT_CODE_LONG_MIN_MS: u32 = 500; // Flashes at least this long are long flashes
+pub const BOOT_CODE_LONG_MAX_MS: u32 = 2000; // Longer flashes are not part of a code
+pub const BOOT_CODE_PAUSE_MS: u32 = 1500; // LED off at least this long ends a sequence
+
 // how long to stay in the watchdog alert state before rebooting
 pub const HOST_WATCHDOG_REBOOT_DURATION_MS: u32 = 5000; // ms
 
@@ -62,8 +160,30 @@ pub const MAX_FLASH_WRITE_QUEUE_DEPTH: usize = 4; // Adjust based on available R
 pub const FLASH_ERASE_BLOCK_SIZE: usize = 4096;
 pub const FLASH_WRITE_BLOCK_SIZE: usize = 4096;
 
+pub const POWER_BUTTON_SHUTDOWN_WINDOW_MS: u32 = 60_000; // CM5 shutdowns within this time of a button press are attributed to it
+pub const EVENT_LOG_CHANNEL_DEPTH: usize = 8; // Pending event log writes
+pub const EVENT_LOG_CHUNK_RECORDS: usize = 8; // Records per I2C read
+pub const MCU_TEMP_ALARM: f32 = 273.15 + 85.0; // K; MCU temperature alarm threshold
+pub const MCU_TEMP_ALARM_HYSTERESIS: f32 = 5.0; // K
+
 pub const FIRMWARE_MARK_BOOTED_DELAY_MS: u32 = 30_000; // Delay before marking firmware as booted
 
+// Guided ADC calibration
+pub const CALIBRATION_NUM_SAMPLES: u32 = 50; // Number of raw ADC samples averaged per calibration pass
+pub const CALIBRATION_SAMPLE_INTERVAL_MS: u32 = 20; // Matches the analog input sampling interval
+pub const CALIBRATION_MIN_RAW_COUNTS: f32 = 100.0; // Minimum average raw reading for a meaningful gain
+pub const CALIBRATION_MIN_CORRECTION_SCALE: f32 = 0.5;
+pub const CALIBRATION_MAX_CORRECTION_SCALE: f32 = 1.5;
+pub const CALIBRATION_MAX_OFFSET_FRACTION: f32 = 0.05; // Maximum offset as a fraction of channel full scale
+
+// Factory test mode timing
+pub const TEST_MODE_STEP_DURATION_MS: u32 = 500; // How long each LED colour or output toggle is held
+pub const TEST_MODE_SETTLE_MS: u32 = 50; // Settling time before reading back an output
+pub const TEST_MODE_BUTTON_TIMEOUT_MS: u32 = 30_000; // How long to wait for the operator to press a button
+// Factory test mode limits. Readings at or near zero mean an open or shorted analog input.
+pub const TEST_MODE_VSCAP_MIN: f32 = 1.0; // V; The supercap charges from VIN during the sequence
+pub const TEST_MODE_IIN_MIN: f32 = 0.01; // A; The controller itself always draws some current
+
 pub const FW_VERSION_STR: &str = "3.3.1";
 
 // Parse version strings into byte arrays
@@ -9,9 +9,9 @@
 //| 3      | USER_BTN    | Input from the user-defined button. Active low.                |
 //| 4      | PCIESLEEP   | Pull high to put the PCIe device to sleep.                     |
 //| 5      | EN3V3OUT    | Enable 3.3V output. Active low.                                |
-//| 6      | GPIO06      | Connected to the GPIO header. Not used.                        |
-//| 7      | GPIO07      | Connected to the GPIO header. Not used.                        |
-//| 8      | GPIO08      | Connected to the GPIO header. Not used.                        |
+//| 6      | GPIO06      | Connected to the GPIO header. Host-controllable GPIO.          |
+//| 7      | GPIO07      | Connected to the GPIO header. Host-controllable GPIO.          |
+//| 8      | GPIO08      | Connected to the GPIO header. Host-controllable GPIO.          |
 //| 9      | PWR_BTN_OUT | Output to the CM5 power button pin. Active low.                |
 //| 10     | LED_PWR     | Power LED state from CM5. Active low.                          |
 //| 11     | LED_ACTIVE  | Active LED state from CM5. Active low.                         |
@@ -32,7 +32,7 @@
 //| 26     | VinS        | Analog: Scaled input voltage level.                            |
 //| 27     | VscapS      | Analog: Scaled supercap voltage level.                         |
 //| 28     | Iin         | Analog: Input current level.                                   |
-//| 29     | GPIO29_ADC3 | Analog: ADC channel 3 input. Unused.                           |
+//| 29     | GPIO29_ADC3 | Analog: ADC channel 3 input. User analog input.                |
 
 use assign_resources::assign_resources;
 use embassy_rp::{Peri, peripherals};
@@ -58,9 +58,6 @@ assign_resources! {
   },
   digital_inputs: DigitalInputResources {
     pcie_led: PIN_1,
-    gpio06: PIN_6,
-    gpio07: PIN_7,
-    gpio08: PIN_8,
     led_pwr: PIN_10,
     led_active: PIN_11,
     cm_on: PIN_13,
@@ -79,6 +76,7 @@ assign_resources! {
     iin: PIN_28,
     gpio29_adc3: PIN_29,
     temp_sensor: ADC_TEMP_SENSOR,
+    dma_ch: DMA_CH2,
   },
   state_machine_outputs: StateMachineOutputResources {
     pcie_sleep: PIN_4,
@@ -94,6 +92,11 @@ assign_resources! {
   power_button: PowerButtonResources {
     pin: PIN_9,
   },
+  header_gpio: HeaderGpioResources {
+    gpio06: PIN_6,
+    gpio07: PIN_7,
+    gpio08: PIN_8,
+  },
   test_mode: TestModeResources {
     pin: PIN_16,
   },
@@ -0,0 +1,296 @@
+// Crash capture and reset reason reporting.
+//
+// Panics, HardFaults and software resets are recorded in a small record in
+// the last 256 bytes of RAM (CRASH_RECORD in memory.x). Neither the bootloader
+// nor the firmware links anything there, so the record survives a reset. At the
+// next boot the record is combined with the RP2040 reset cause registers into
+// a reset report, which is readable over I2C and copied to the flash event log.
+// The record also carries the last shutdown reason (see tasks::shutdown_reason)
+// and the software clock (see rtc).
+
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+
+use cortex_m::peripheral::SCB;
+use cortex_m_rt::{ExceptionFrame, exception};
+use defmt::{error, info};
+use embassy_rp::pac;
+use embassy_sync::once_lock::OnceLock;
+
+use crate::rtc::{rtc_drift_ppm, rtc_epoch_ms};
+
+/// Maximum stored panic message length in bytes
+pub const CRASH_MESSAGE_MAX_LEN: usize = 64;
+
+const CRASH_RECORD_MAGIC: u32 = 0x4352_5332; // "CRS2", bumped when the layout changes
+
+/// Reset causes. The discriminant is part of the I2C API and the event log format.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum ResetReason {
+    Unknown = 0,
+    /// Power-on or brownout reset
+    PowerOn = 1,
+    /// RUN pin reset
+    RunPin = 2,
+    /// Reset from the debugger
+    Debugger = 3,
+    /// The hardware watchdog expired
+    Watchdog = 4,
+    /// Reset forced through the watchdog
+    WatchdogForced = 5,
+    /// Software reset requested by the firmware
+    Software = 6,
+    /// Reset after a panic
+    Panic = 7,
+    /// Reset after a HardFault
+    HardFault = 8,
+}
+
+/// Crash kinds stored in the no-init record
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum CrashKind {
+    None = 0,
+    Panic = 1,
+    HardFault = 2,
+}
+
+/// Record kept in no-init RAM across resets
+#[repr(C)]
+struct CrashRecord {
+    magic: u32,
+    software_reset: u32,
+    kind: u32,
+    /// Faulting PC (HardFault) or panic line number
+    pc: u32,
+    lr: u32,
+    message_len: u32,
+    message: [u8; CRASH_MESSAGE_MAX_LEN],
+    /// Packed `ShutdownRecord`, 0 if none
+    shutdown: u32,
+    /// Software clock at the time of a software reset, 0 if not set
+    rtc_epoch_ms: u64,
+    rtc_drift_ppm: i32,
+}
+
+unsafe extern "C" {
+    static mut __crash_record_start: MaybeUninit<CrashRecord>;
+}
+
+/// Size of the CRASH_RECORD region in memory.x
+const CRASH_RECORD_REGION_SIZE: usize = 256;
+const _: () = assert!(core::mem::size_of::<CrashRecord>() <= CRASH_RECORD_REGION_SIZE);
+
+/// What the previous run left behind, captured at boot
+#[derive(Clone, Copy)]
+pub struct ResetReport {
+    pub reason: ResetReason,
+    pub crash_kind: CrashKind,
+    /// Faulting PC for a HardFault, source line for a panic
+    pub pc: u32,
+    /// Link register for a HardFault
+    pub lr: u32,
+    pub message_len: u8,
+    pub message: [u8; CRASH_MESSAGE_MAX_LEN],
+    /// Packed shutdown record of the previous run, 0 if none
+    pub shutdown: u32,
+    /// Software clock at the end of the previous run, 0 if not set
+    pub rtc_epoch_ms: u64,
+    pub rtc_drift_ppm: i32,
+}
+
+/// Size of the reset report in the I2C wire format
+pub const RESET_REPORT_SIZE: usize = 11 + CRASH_MESSAGE_MAX_LEN;
+
+impl ResetReport {
+    /// I2C wire format: reset reason, crash kind, PC (u32 BE), LR (u32 BE),
+    /// message length, message (zero padded)
+    pub fn to_bytes(&self) -> [u8; RESET_REPORT_SIZE] {
+        let mut bytes = [0u8; RESET_REPORT_SIZE];
+        bytes[0] = self.reason as u8;
+        bytes[1] = self.crash_kind as u8;
+        bytes[2..6].copy_from_slice(&self.pc.to_be_bytes());
+        bytes[6..10].copy_from_slice(&self.lr.to_be_bytes());
+        bytes[10] = self.message_len;
+        bytes[11..].copy_from_slice(&self.message);
+        bytes
+    }
+}
+
+static RESET_REPORT: OnceLock<ResetReport> = OnceLock::new();
+
+fn crash_record() -> *mut CrashRecord {
+    (&raw mut __crash_record_start).cast()
+}
+
+/// Reset cause from the RP2040 reset registers
+fn hardware_reset_reason() -> ResetReason {
+    let watchdog = pac::WATCHDOG.reason().read();
+    let chip_reset = pac::VREG_AND_CHIP_RESET.chip_reset().read();
+    if watchdog.timer() {
+        ResetReason::Watchdog
+    } else if watchdog.force() {
+        ResetReason::WatchdogForced
+    } else if chip_reset.had_psm_restart() {
+        ResetReason::Debugger
+    } else if chip_reset.had_run() {
+        ResetReason::RunPin
+    } else if chip_reset.had_por() {
+        ResetReason::PowerOn
+    } else {
+        ResetReason::Unknown
+    }
+}
+
+/// Capture the reset report and clear the no-init record. Must be called once, early at boot.
+pub fn init_crash_log() {
+    let record = crash_record();
+    // Safety: nothing else accesses the record before the report has been captured
+    let report = unsafe {
+        let valid = (*record).magic == CRASH_RECORD_MAGIC;
+        let kind = match (valid, (*record).kind) {
+            (true, 1) => CrashKind::Panic,
+            (true, 2) => CrashKind::HardFault,
+            _ => CrashKind::None,
+        };
+        let reason = match kind {
+            CrashKind::Panic => ResetReason::Panic,
+            CrashKind::HardFault => ResetReason::HardFault,
+            CrashKind::None if valid && (*record).software_reset != 0 => ResetReason::Software,
+            CrashKind::None => hardware_reset_reason(),
+        };
+        let mut report = ResetReport {
+            reason,
+            crash_kind: kind,
+            pc: 0,
+            lr: 0,
+            message_len: 0,
+            message: [0; CRASH_MESSAGE_MAX_LEN],
+            shutdown: if valid { (*record).shutdown } else { 0 },
+            rtc_epoch_ms: 0,
+            rtc_drift_ppm: 0,
+        };
+        // The clock is only carried over a software reset, where it was saved just before
+        if reason == ResetReason::Software {
+            report.rtc_epoch_ms = (*record).rtc_epoch_ms;
+            report.rtc_drift_ppm = (*record).rtc_drift_ppm;
+        }
+        if kind != CrashKind::None {
+            report.pc = (*record).pc;
+            report.lr = (*record).lr;
+            report.message_len = ((*record).message_len as usize).min(CRASH_MESSAGE_MAX_LEN) as u8;
+            report.message = (*record).message;
+        }
+        record.write(CrashRecord {
+            magic: CRASH_RECORD_MAGIC,
+            software_reset: 0,
+            kind: CrashKind::None as u32,
+            pc: 0,
+            lr: 0,
+            message_len: 0,
+            message: [0; CRASH_MESSAGE_MAX_LEN],
+            shutdown: 0,
+            rtc_epoch_ms: 0,
+            rtc_drift_ppm: 0,
+        });
+        report
+    };
+
+    info!(
+        "Reset reason: {}, crash: {}, pc: {:08x}",
+        report.reason, report.crash_kind, report.pc
+    );
+    if RESET_REPORT.init(report).is_err() {
+        error!("Reset report already initialized");
+    }
+}
+
+pub async fn get_reset_report() -> ResetReport {
+    *RESET_REPORT.get().await
+}
+
+/// The reset report, if `init_crash_log` has run
+pub fn try_get_reset_report() -> Option<ResetReport> {
+    RESET_REPORT.try_get().copied()
+}
+
+/// Keep a packed shutdown record across the next reset
+pub fn record_shutdown_in_noinit(shutdown: u32) {
+    // Safety: the record is initialized at boot and only written with single word stores
+    unsafe {
+        (*crash_record()).shutdown = shutdown;
+    }
+}
+
+/// Reset the system, recording the reset as software-requested and keeping the clock
+pub fn software_reset() -> ! {
+    let rtc_epoch_ms = rtc_epoch_ms().unwrap_or(0);
+    let rtc_drift_ppm = rtc_drift_ppm();
+    // Safety: the record is only written here and in the fault handlers, which do not return
+    unsafe {
+        let record = crash_record();
+        (*record).magic = CRASH_RECORD_MAGIC;
+        (*record).software_reset = 1;
+        (*record).rtc_epoch_ms = rtc_epoch_ms;
+        (*record).rtc_drift_ppm = rtc_drift_ppm;
+    }
+    SCB::sys_reset();
+}
+
+/// Truncating writer into the crash record message buffer
+struct MessageWriter<'a> {
+    buffer: &'a mut [u8; CRASH_MESSAGE_MAX_LEN],
+    len: usize,
+}
+
+impl Write for MessageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let n = s.len().min(CRASH_MESSAGE_MAX_LEN - self.len);
+        self.buffer[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    error!("{}", defmt::Display2Format(info));
+    cortex_m::interrupt::disable();
+
+    // Safety: interrupts are disabled and the handler does not return
+    unsafe {
+        let record = crash_record();
+        (*record).magic = CRASH_RECORD_MAGIC;
+        (*record).software_reset = 0;
+        (*record).kind = CrashKind::Panic as u32;
+        (*record).pc = info.location().map_or(0, |location| location.line());
+        (*record).lr = 0;
+        let mut writer = MessageWriter {
+            buffer: &mut (*record).message,
+            len: 0,
+        };
+        if let Some(location) = info.location() {
+            let _ = write!(writer, "{}:{}: ", location.file(), location.line());
+        }
+        let _ = write!(writer, "{}", info.message());
+        (*record).message_len = writer.len as u32;
+    }
+    SCB::sys_reset();
+}
+
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    // Safety: the handler does not return
+    unsafe {
+        let record = crash_record();
+        (*record).magic = CRASH_RECORD_MAGIC;
+        (*record).software_reset = 0;
+        (*record).kind = CrashKind::HardFault as u32;
+        (*record).pc = frame.pc();
+        (*record).lr = frame.lr();
+        (*record).message_len = 0;
+    }
+    SCB::sys_reset();
+}
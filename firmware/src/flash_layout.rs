@@ -12,6 +12,12 @@ unsafe extern "C" {
 
     static __bootloader_appdata_start: u32;
     static __bootloader_appdata_end: u32;
+
+    static __eventlog_start: u32;
+    static __eventlog_end: u32;
+
+    static __telemetry_start: u32;
+    static __telemetry_end: u32;
 }
 
 /// The size of a page in bytes
@@ -38,6 +44,20 @@ pub fn get_bootloader_appdata_range() -> Range<u32> {
         start..end
     }
 }
+pub fn get_eventlog_range() -> Range<u32> {
+    unsafe {
+        let start = &__eventlog_start as *const u32 as u32;
+        let end = &__eventlog_end as *const u32 as u32;
+        start..end
+    }
+}
+pub fn get_telemetry_range() -> Range<u32> {
+    unsafe {
+        let start = &__telemetry_start as *const u32 as u32;
+        let end = &__telemetry_end as *const u32 as u32;
+        start..end
+    }
+}
 pub fn get_bootloader_state_size() -> u32 {
     get_bootloader_state_range().end - get_bootloader_state_range().start
 }
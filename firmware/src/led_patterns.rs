@@ -1,5 +1,6 @@
 use alloc::boxed::Box;
 use alloc::vec;
+use smart_leds::RGB8;
 use smart_leds::colors::*;
 
 use crate::tasks::led_blinker::*;
@@ -40,9 +41,41 @@ pub fn get_state_pattern(state: &State) -> LEDPattern {
         State::HostUnresponsive { .. } => LEDPattern::new(vec![Box::new(OneColor::new(100, RED))]),
         State::EnteringStandby { .. } => LEDPattern::new(vec![Box::new(OneColor::new(100, BLUE))]),
         State::Standby {} => LEDPattern::new(vec![Box::new(OneColor::new(100, DARK_RED))]),
+        State::TestMode {} => LEDPattern::new(vec![Box::new(OneColor::new(100, WHITE))]),
     }
 }
 
+pub fn get_test_color_pattern(color: RGB8) -> LEDPattern {
+    LEDPattern::new(vec![Box::new(OneColor::new(100, color))])
+}
+
+pub fn get_test_result_pattern(passed: bool) -> LEDPattern {
+    if passed {
+        LEDPattern::new(vec![Box::new(OneColor::new(100, GREEN))])
+    } else {
+        LEDPattern::new(vec![
+            Box::new(OneColor::new(250, RED)),
+            Box::new(OneColor::new(250, BLACK)),
+        ])
+    }
+}
+
+/// Repeat a CM5 boot error code on the whole bar, like the CM5 activity LED: long red
+/// flashes, short red flashes, then a pause
+pub fn get_boot_code_pattern(long_flashes: u8, short_flashes: u8) -> LEDPattern {
+    let mut fragments: FragmentVec = vec![];
+    for _ in 0..long_flashes {
+        fragments.push(Box::new(OneColor::new(1000, RED)));
+        fragments.push(Box::new(Off::new(500)));
+    }
+    for _ in 0..short_flashes {
+        fragments.push(Box::new(OneColor::new(250, RED)));
+        fragments.push(Box::new(Off::new(500)));
+    }
+    fragments.push(Box::new(Off::new(2000)));
+    LEDPattern::new(fragments)
+}
+
 pub fn get_vscap_alarm_pattern() -> LEDPattern {
     LEDPattern::new(vec![
         Box::new(OneColor::new(100, RED)),
@@ -18,16 +18,19 @@ const HEAP_SIZE: usize = 65536; // 64kB
 use defmt::{error, info};
 use embassy_executor::Spawner;
 
-use {defmt_rtt as _, panic_probe as _};
+use defmt_rtt as _;
 
+mod analog_filter;
 mod config;
 mod config_resources;
+mod crash_log;
 mod flash_layout;
 mod led_patterns;
+mod rtc;
 mod tasks;
 
 use crate::config_resources::{
-    AnalogInputResources, AssignedResources, ConfigManagerOutputResources, DigitalInputResources, I2CPeripheralsResources,
+    AnalogInputResources, AssignedResources, ConfigManagerOutputResources, DigitalInputResources, HeaderGpioResources, I2CPeripheralsResources,
     I2CSecondaryResources, PowerButtonInputResources, PowerButtonResources, RGBLEDResources,
     StateMachineOutputResources, TestModeResources, UserButtonInputResources,
 };
@@ -51,6 +54,9 @@ async fn main(spawner: Spawner) {
 
     let p = embassy_rp::init(Default::default());
 
+    crash_log::init_crash_log();
+    rtc::init_rtc();
+
     let r = split_resources!(p);
 
     info!("Starting up...");
@@ -100,6 +106,18 @@ async fn main(spawner: Spawner) {
         .spawn(tasks::gpio_input::test_mode_input_task(r.test_mode))
         .unwrap();
 
+    spawner
+        .spawn(tasks::header_gpio::header_gpio_task(r.header_gpio))
+        .unwrap();
+
+    spawner
+        .spawn(tasks::test_mode::test_mode_task())
+        .unwrap();
+
+    spawner
+        .spawn(tasks::calibration::calibration_task())
+        .unwrap();
+
     spawner
         .spawn(tasks::power_button::power_button_output_task(
             r.power_button,
@@ -141,4 +159,20 @@ async fn main(spawner: Spawner) {
     spawner
         .spawn(tasks::config_manager::config_manager_task(flash, r.config_manager_outputs))
         .unwrap();
+
+    spawner
+        .spawn(tasks::event_log::event_log_task(flash))
+        .unwrap();
+
+    spawner
+        .spawn(tasks::telemetry_log::telemetry_log_task(flash))
+        .unwrap();
+
+    spawner
+        .spawn(tasks::lifetime_stats::lifetime_stats_task())
+        .unwrap();
+
+    spawner
+        .spawn(tasks::telemetry_stats::telemetry_stats_task())
+        .unwrap();
 }
@@ -0,0 +1,148 @@
+// Software real-time clock.
+//
+// The host sets the UTC time over I2C; the controller then counts it forward
+// from its uptime timer. Repeated host sets at least an hour apart are used to
+// estimate the drift of the timer, which is corrected from then on. The time
+// and the drift estimate are kept in the no-init crash record across software
+// resets, so the clock keeps running when the controller resets after a
+// power-down. Event log and telemetry records are timestamped with it once set.
+//
+// The clock is accessed from the reset path, so it uses a blocking mutex.
+
+use core::cell::Cell;
+
+use defmt::info;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::Instant;
+
+use crate::config::{RTC_DRIFT_MIN_INTERVAL_MS, RTC_MAX_DRIFT_PPM};
+use crate::crash_log::try_get_reset_report;
+
+/// Where the current time came from. The discriminant is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum RtcSource {
+    NotSet = 0,
+    /// Set by the host during this run
+    Host = 1,
+    /// Carried over from the previous run across a software reset
+    Restored = 2,
+}
+
+#[derive(Clone, Copy)]
+struct RtcState {
+    source: RtcSource,
+    /// UTC time in milliseconds since the Unix epoch at `base`
+    base_epoch_ms: u64,
+    base: Instant,
+    drift_ppm: i32,
+    /// Last host set used as the reference for drift estimation
+    sync_epoch_ms: u64,
+    sync: Option<Instant>,
+}
+
+static RTC: Mutex<CriticalSectionRawMutex, Cell<RtcState>> = Mutex::new(Cell::new(RtcState {
+    source: RtcSource::NotSet,
+    base_epoch_ms: 0,
+    base: Instant::from_ticks(0),
+    drift_ppm: 0,
+    sync_epoch_ms: 0,
+    sync: None,
+}));
+
+/// Size of the clock status in the I2C wire format
+pub const RTC_STATUS_SIZE: usize = 11;
+
+impl RtcState {
+    fn epoch_ms_at(&self, now: Instant) -> u64 {
+        let elapsed_ms = now.duration_since(self.base).as_millis() as i64;
+        let correction_ms = elapsed_ms * self.drift_ppm as i64 / 1_000_000;
+        (self.base_epoch_ms as i64 + elapsed_ms + correction_ms) as u64
+    }
+}
+
+/// Restore the clock kept across a software reset. Must be called after `init_crash_log`.
+pub fn init_rtc() {
+    let Some(report) = try_get_reset_report() else {
+        return;
+    };
+    if report.rtc_epoch_ms == 0 {
+        return;
+    }
+    info!(
+        "Restored RTC: {} ms, drift {} ppm",
+        report.rtc_epoch_ms, report.rtc_drift_ppm
+    );
+    RTC.lock(|rtc| {
+        rtc.set(RtcState {
+            source: RtcSource::Restored,
+            base_epoch_ms: report.rtc_epoch_ms,
+            // The reset took a few milliseconds; count the time from the start of this run
+            base: Instant::from_ticks(0),
+            drift_ppm: report.rtc_drift_ppm,
+            sync_epoch_ms: 0,
+            sync: None,
+        })
+    });
+}
+
+/// Current UTC time in milliseconds since the Unix epoch, if the clock has been set
+pub fn rtc_epoch_ms() -> Option<u64> {
+    let state = RTC.lock(|rtc| rtc.get());
+    match state.source {
+        RtcSource::NotSet => None,
+        _ => Some(state.epoch_ms_at(Instant::now())),
+    }
+}
+
+/// Current UTC time in seconds since the Unix epoch, if the clock has been set
+pub fn rtc_epoch_s() -> Option<u32> {
+    rtc_epoch_ms().map(|ms| (ms / 1000) as u32)
+}
+
+/// Drift estimate in ppm, for carrying it across a reset
+pub fn rtc_drift_ppm() -> i32 {
+    RTC.lock(|rtc| rtc.get().drift_ppm)
+}
+
+/// Set the clock to the host time. If the previous host set is long enough ago, the
+/// difference between the host and the local clock updates the drift estimate.
+pub fn set_rtc_epoch_ms(epoch_ms: u64) {
+    let now = Instant::now();
+    RTC.lock(|rtc| {
+        let mut state = rtc.get();
+        if let Some(sync) = state.sync {
+            let local_ms = now.duration_since(sync).as_millis() as i64;
+            if local_ms >= RTC_DRIFT_MIN_INTERVAL_MS as i64 {
+                let host_ms = epoch_ms as i64 - state.sync_epoch_ms as i64;
+                let drift_ppm = (host_ms - local_ms) * 1_000_000 / local_ms;
+                if drift_ppm.abs() <= RTC_MAX_DRIFT_PPM as i64 {
+                    info!("RTC drift estimate: {} ppm", drift_ppm);
+                    state.drift_ppm = drift_ppm as i32;
+                }
+                state.sync = None;
+            }
+        }
+        if state.sync.is_none() {
+            state.sync = Some(now);
+            state.sync_epoch_ms = epoch_ms;
+        }
+        state.source = RtcSource::Host;
+        state.base_epoch_ms = epoch_ms;
+        state.base = now;
+        rtc.set(state);
+    });
+    info!("RTC set to {} ms", epoch_ms);
+}
+
+/// Clock status in I2C wire format: source (see `RtcSource`), UTC time in milliseconds
+/// since the Unix epoch (u64 BE, 0 if not set), drift correction in ppm (i16 BE)
+pub fn get_rtc_status() -> [u8; RTC_STATUS_SIZE] {
+    let state = RTC.lock(|rtc| rtc.get());
+    let mut bytes = [0u8; RTC_STATUS_SIZE];
+    bytes[0] = state.source as u8;
+    bytes[1..9].copy_from_slice(&rtc_epoch_ms().unwrap_or(0).to_be_bytes());
+    bytes[9..11].copy_from_slice(&(state.drift_ppm as i16).to_be_bytes());
+    bytes
+}
@@ -0,0 +1,207 @@
+//! CM5 boot error code decoder.
+//!
+//! When the CM5 bootloader cannot boot (no boot media, bad firmware, SDRAM
+//! failure, ...), it reports the error by flashing the activity LED: a number
+//! of long flashes, then a number of short flashes, then a pause, repeated.
+//! The digital input task feeds LED_nACT into the decoder, which accepts a code
+//! once the same sequence has been seen twice in a row. Decoding is armed when
+//! the state machine enters SystemStartup and runs for the boot window, as the
+//! bootloader only starts once the CM5 3.3V rail is up. The first I2C transaction
+//! from the host shows that the CM5 has booted and disarms the decoder, so normal
+//! disk activity is never mistaken for a code. The state machine shows a decoded
+//! code on the LED bar, and the host reads it over I2C (register 0x28).
+
+use defmt::warn;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::config::{
+    BOOT_CODE_LONG_MAX_MS, BOOT_CODE_LONG_MIN_MS, BOOT_CODE_PAUSE_MS, BOOT_CODE_SHORT_MIN_MS,
+    BOOT_CODE_WINDOW_MS,
+};
+use crate::tasks::event_log::{LogEventKind, log_event};
+
+/// Size of the boot code status in the I2C wire format
+pub const BOOT_CODE_STATUS_SIZE: usize = 3;
+
+/// A boot error code as flashed by the CM5 bootloader
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct BootCode {
+    pub long_flashes: u8,
+    pub short_flashes: u8,
+}
+
+/// Decoder states. The discriminant is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+enum DecoderStatus {
+    /// Outside the boot window, no code decoded
+    Idle = 0,
+    /// Watching the activity LED
+    Decoding = 1,
+    /// A code was decoded during the last boot
+    Decoded = 2,
+}
+
+struct Decoder {
+    armed_until: Option<Instant>,
+    lit: bool,
+    /// Last change of the LED
+    since: Instant,
+    long_flashes: u8,
+    short_flashes: u8,
+    /// Whether all flashes of the current sequence had a valid length and order
+    valid: bool,
+    /// Previous complete sequence, which the current one must repeat
+    previous: Option<BootCode>,
+    code: Option<BootCode>,
+    /// Set when `code` changes, cleared when the state machine takes it
+    new_code: bool,
+    /// Whether the host has been active since the decoder was armed
+    host_seen: bool,
+}
+
+static DECODER: Mutex<CriticalSectionRawMutex, Decoder> = Mutex::new(Decoder {
+    armed_until: None,
+    lit: false,
+    since: Instant::from_ticks(0),
+    long_flashes: 0,
+    short_flashes: 0,
+    valid: true,
+    previous: None,
+    code: None,
+    new_code: false,
+    host_seen: false,
+});
+
+impl Decoder {
+    fn start_sequence(&mut self) {
+        self.long_flashes = 0;
+        self.short_flashes = 0;
+        self.valid = true;
+    }
+
+    /// Classify a flash that lasted `ms`. Long flashes come first.
+    fn end_flash(&mut self, ms: u64) {
+        if (BOOT_CODE_SHORT_MIN_MS as u64..BOOT_CODE_LONG_MIN_MS as u64).contains(&ms) {
+            self.short_flashes = self.short_flashes.saturating_add(1);
+        } else if (BOOT_CODE_LONG_MIN_MS as u64..=BOOT_CODE_LONG_MAX_MS as u64).contains(&ms)
+            && self.short_flashes == 0
+        {
+            self.long_flashes = self.long_flashes.saturating_add(1);
+        } else {
+            self.valid = false;
+        }
+    }
+
+    /// Handle the pause that ends a sequence
+    fn end_sequence(&mut self) {
+        let sequence = BootCode {
+            long_flashes: self.long_flashes,
+            short_flashes: self.short_flashes,
+        };
+        let complete = self.valid && sequence.short_flashes > 0;
+        self.start_sequence();
+        if !complete {
+            self.previous = None;
+            return;
+        }
+        if self.previous == Some(sequence) && self.code != Some(sequence) {
+            warn!("CM5 boot error code: {}", sequence);
+            log_event(
+                LogEventKind::BootError,
+                ((sequence.long_flashes as u32) << 8) | sequence.short_flashes as u32,
+            );
+            self.code = Some(sequence);
+            self.new_code = true;
+        }
+        self.previous = Some(sequence);
+    }
+}
+
+/// Record a sample of the LED_nACT line (active low)
+pub async fn record_boot_code_sample(led_active: bool) {
+    let now = Instant::now();
+    let mut decoder = DECODER.lock().await;
+    match decoder.armed_until {
+        Some(until) if now < until => {}
+        Some(_) => {
+            decoder.armed_until = None;
+            return;
+        }
+        None => return,
+    }
+
+    let lit = !led_active;
+    let elapsed_ms = now.duration_since(decoder.since).as_millis();
+    if lit != decoder.lit {
+        if decoder.lit {
+            decoder.end_flash(elapsed_ms);
+        }
+        decoder.lit = lit;
+        decoder.since = now;
+    } else if lit && elapsed_ms > BOOT_CODE_LONG_MAX_MS as u64 {
+        // Steady on is not a flash code
+        decoder.valid = false;
+        decoder.previous = None;
+    } else if !lit
+        && elapsed_ms >= BOOT_CODE_PAUSE_MS as u64
+        && (decoder.long_flashes > 0 || decoder.short_flashes > 0 || !decoder.valid)
+    {
+        decoder.end_sequence();
+    }
+}
+
+/// Forget the previous code and watch the activity LED for the boot window. Called when
+/// the state machine enters SystemStartup.
+pub async fn start_boot_code_decoding() {
+    let now = Instant::now();
+    let mut decoder = DECODER.lock().await;
+    decoder.armed_until = Some(now + Duration::from_millis(BOOT_CODE_WINDOW_MS as u64));
+    decoder.lit = false;
+    decoder.since = now;
+    decoder.start_sequence();
+    decoder.previous = None;
+    decoder.code = None;
+    decoder.new_code = false;
+    decoder.host_seen = false;
+}
+
+/// Stop decoding because the host is up. Called on every I2C transaction; a code
+/// decoded before is still reported over I2C but no longer shown on the LEDs.
+pub async fn disarm_boot_code_decoding() {
+    let mut decoder = DECODER.lock().await;
+    if decoder.host_seen {
+        return;
+    }
+    decoder.host_seen = true;
+    decoder.armed_until = None;
+    decoder.new_code = false;
+}
+
+/// A code decoded since the previous call, if any. Always None once the host has been seen.
+pub async fn take_boot_code() -> Option<BootCode> {
+    let mut decoder = DECODER.lock().await;
+    if !decoder.new_code || decoder.host_seen {
+        return None;
+    }
+    decoder.new_code = false;
+    decoder.code
+}
+
+/// Boot code status in I2C wire format: decoder status (0 = idle, 1 = decoding,
+/// 2 = code decoded), number of long flashes, number of short flashes
+pub async fn get_boot_code_status() -> [u8; BOOT_CODE_STATUS_SIZE] {
+    let decoder = DECODER.lock().await;
+    let status = match (decoder.code, decoder.armed_until) {
+        (Some(_), _) => DecoderStatus::Decoded,
+        (None, Some(until)) if Instant::now() < until => DecoderStatus::Decoding,
+        (None, _) => DecoderStatus::Idle,
+    };
+    let code = decoder.code.unwrap_or(BootCode {
+        long_flashes: 0,
+        short_flashes: 0,
+    });
+    [status as u8, code.long_flashes, code.short_flashes]
+}
@@ -0,0 +1,333 @@
+//! Guided ADC calibration.
+//!
+//! A bench technician applies a known voltage or current to the board and tells
+//! the controller the true value over I2C (register 0x58). The controller averages
+//! the raw ADC counts, computes the correction scale, checks it against sane bounds
+//! and persists it through the config manager. A second averaging pass with the new
+//! scale applied gives the residual error reported back to the technician.
+//!
+//! Two-point calibration (register 0x59) captures the raw counts at two known
+//! values and derives both the gain and the offset of the channel.
+
+use defmt::{info, warn};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Ticker};
+
+use crate::config::*;
+use crate::tasks::config_manager::{
+    get_iin_correction_offset, get_vin_correction_offset, get_vscap_correction_offset,
+    set_iin_correction_offset, set_iin_correction_scale, set_vin_correction_offset,
+    set_vin_correction_scale, set_vscap_correction_offset, set_vscap_correction_scale,
+};
+use crate::tasks::gpio_input::{IIN_ADC_SCALE, INPUTS, Inputs, VIN_ADC_SCALE, VSCAP_ADC_SCALE};
+
+/// Analog channels that can be calibrated. The discriminant is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum CalibrationChannel {
+    Vin = 0,
+    Vscap = 1,
+    Iin = 2,
+}
+
+impl CalibrationChannel {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Vin),
+            1 => Some(Self::Vscap),
+            2 => Some(Self::Iin),
+            _ => None,
+        }
+    }
+
+    fn raw_counts(&self, inputs: &Inputs) -> u16 {
+        match self {
+            Self::Vin => inputs.vin_raw,
+            Self::Vscap => inputs.vscap_raw,
+            Self::Iin => inputs.iin_raw,
+        }
+    }
+
+    fn adc_scale(&self) -> f32 {
+        match self {
+            Self::Vin => VIN_ADC_SCALE,
+            Self::Vscap => VSCAP_ADC_SCALE,
+            Self::Iin => IIN_ADC_SCALE,
+        }
+    }
+
+    fn full_scale(&self) -> f32 {
+        match self {
+            Self::Vin => VIN_MAX_VALUE,
+            Self::Vscap => VSCAP_MAX_VALUE,
+            Self::Iin => IIN_MAX_VALUE,
+        }
+    }
+
+    async fn correction_offset(&self) -> f32 {
+        match self {
+            Self::Vin => get_vin_correction_offset().await,
+            Self::Vscap => get_vscap_correction_offset().await,
+            Self::Iin => get_iin_correction_offset().await,
+        }
+    }
+
+    async fn store_correction_scale(&self, value: f32) {
+        match self {
+            Self::Vin => set_vin_correction_scale(value).await,
+            Self::Vscap => set_vscap_correction_scale(value).await,
+            Self::Iin => set_iin_correction_scale(value).await,
+        }
+    }
+
+    async fn store_correction_offset(&self, value: f32) {
+        match self {
+            Self::Vin => set_vin_correction_offset(value).await,
+            Self::Vscap => set_vscap_correction_offset(value).await,
+            Self::Iin => set_iin_correction_offset(value).await,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum CalibrationStatus {
+    Idle = 0,
+    InProgress = 1,
+    Done = 2,
+    /// The computed correction scale is outside the allowed bounds and was not stored
+    OutOfBounds = 3,
+    /// The ADC reading is too low to compute a meaningful correction
+    NoSignal = 4,
+    /// The first point of a two-point calibration was captured
+    PointCaptured = 5,
+    /// The second point of a two-point calibration was requested without a first point
+    MissingFirstPoint = 6,
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub enum CalibrationKind {
+    /// Compute the gain only, keeping the stored offset
+    SinglePoint,
+    /// Capture the first point of a two-point calibration
+    FirstPoint,
+    /// Capture the second point and compute gain and offset
+    SecondPoint,
+}
+
+pub struct CalibrationRequest {
+    pub kind: CalibrationKind,
+    pub channel: CalibrationChannel,
+    /// True value in V (voltage channels) or A (current channel)
+    pub true_value: f32,
+}
+
+struct CalibrationResult {
+    status: CalibrationStatus,
+    channel: CalibrationChannel,
+    /// Residual error after calibration in mV or mA
+    residual_error: i32,
+    correction_scale: f32,
+    correction_offset: f32,
+}
+
+impl CalibrationResult {
+    const fn new() -> Self {
+        Self {
+            status: CalibrationStatus::Idle,
+            channel: CalibrationChannel::Vin,
+            residual_error: 0,
+            correction_scale: 0.0,
+            correction_offset: 0.0,
+        }
+    }
+}
+
+static CALIBRATION_RESULT: Mutex<CriticalSectionRawMutex, CalibrationResult> =
+    Mutex::new(CalibrationResult::new());
+
+pub type CalibrationChannelType = channel::Channel<CriticalSectionRawMutex, CalibrationRequest, 2>;
+pub static CALIBRATION_REQUEST_CHANNEL: CalibrationChannelType = channel::Channel::new();
+
+/// Start a single-point calibration run. `true_value_milli` is the true value in mV or mA.
+pub async fn start_calibration(channel: CalibrationChannel, true_value_milli: u32) {
+    request_calibration(CalibrationKind::SinglePoint, channel, true_value_milli).await;
+}
+
+/// Capture a two-point calibration point. `true_value_milli` is the true value in mV or mA.
+pub async fn start_two_point_calibration(
+    channel: CalibrationChannel,
+    second_point: bool,
+    true_value_milli: u32,
+) {
+    let kind = if second_point {
+        CalibrationKind::SecondPoint
+    } else {
+        CalibrationKind::FirstPoint
+    };
+    request_calibration(kind, channel, true_value_milli).await;
+}
+
+async fn request_calibration(
+    kind: CalibrationKind,
+    channel: CalibrationChannel,
+    true_value_milli: u32,
+) {
+    {
+        let mut result = CALIBRATION_RESULT.lock().await;
+        if result.status == CalibrationStatus::InProgress {
+            warn!("Calibration already in progress");
+            return;
+        }
+        result.status = CalibrationStatus::InProgress;
+        result.channel = channel;
+    }
+    CALIBRATION_REQUEST_CHANNEL
+        .send(CalibrationRequest {
+            kind,
+            channel,
+            true_value: true_value_milli as f32 / 1000.0,
+        })
+        .await;
+}
+
+/// Get the calibration result in I2C wire format:
+/// status (u8), channel (u8), residual error (i32 BE, mV or mA), correction scale (f32 LE),
+/// correction offset (f32 LE)
+pub async fn get_calibration_result() -> [u8; 14] {
+    let result = CALIBRATION_RESULT.lock().await;
+    let mut bytes = [0u8; 14];
+    bytes[0] = result.status as u8;
+    bytes[1] = result.channel as u8;
+    bytes[2..6].copy_from_slice(&result.residual_error.to_be_bytes());
+    bytes[6..10].copy_from_slice(&result.correction_scale.to_le_bytes());
+    bytes[10..14].copy_from_slice(&result.correction_offset.to_le_bytes());
+    bytes
+}
+
+/// Average the raw ADC counts of a channel over the calibration window
+async fn average_raw_counts(channel: CalibrationChannel) -> f32 {
+    let mut ticker = Ticker::every(Duration::from_millis(CALIBRATION_SAMPLE_INTERVAL_MS as u64));
+    let mut sum = 0u32;
+    for _ in 0..CALIBRATION_NUM_SAMPLES {
+        ticker.next().await;
+        sum += channel.raw_counts(&*INPUTS.lock().await) as u32;
+    }
+    sum as f32 / CALIBRATION_NUM_SAMPLES as f32
+}
+
+fn scale_in_bounds(correction_scale: f32) -> bool {
+    (CALIBRATION_MIN_CORRECTION_SCALE..=CALIBRATION_MAX_CORRECTION_SCALE).contains(&correction_scale)
+}
+
+async fn set_status(status: CalibrationStatus) {
+    CALIBRATION_RESULT.lock().await.status = status;
+}
+
+/// Store the new correction, then verify it with a fresh set of samples
+async fn apply_calibration(
+    channel: CalibrationChannel,
+    correction_scale: f32,
+    correction_offset: f32,
+    true_value: f32,
+) {
+    channel.store_correction_scale(correction_scale).await;
+    channel.store_correction_offset(correction_offset).await;
+
+    let raw = average_raw_counts(channel).await;
+    let residual = raw * channel.adc_scale() * correction_scale + correction_offset - true_value;
+    info!(
+        "Calibration of {:?} done: scale {}, offset {}, residual {}",
+        channel, correction_scale, correction_offset, residual
+    );
+
+    let mut result = CALIBRATION_RESULT.lock().await;
+    result.status = CalibrationStatus::Done;
+    result.residual_error = (residual * 1000.0) as i32;
+    result.correction_scale = correction_scale;
+    result.correction_offset = correction_offset;
+}
+
+async fn run_calibration(
+    request: CalibrationRequest,
+    first_point: &mut Option<(CalibrationChannel, f32, f32)>,
+) {
+    let channel = request.channel;
+    info!(
+        "Calibrating {:?} ({:?}) against true value {}",
+        channel, request.kind, request.true_value
+    );
+
+    if request.kind == CalibrationKind::SecondPoint
+        && !matches!(first_point, Some((c, _, _)) if *c == channel)
+    {
+        warn!("Second calibration point without a first point");
+        set_status(CalibrationStatus::MissingFirstPoint).await;
+        return;
+    }
+
+    let raw = average_raw_counts(channel).await;
+    if request.kind == CalibrationKind::SinglePoint && raw < CALIBRATION_MIN_RAW_COUNTS {
+        warn!("Calibration signal too low: {} counts", raw);
+        set_status(CalibrationStatus::NoSignal).await;
+        return;
+    }
+
+    let (correction_scale, correction_offset) = match request.kind {
+        CalibrationKind::FirstPoint => {
+            *first_point = Some((channel, raw, request.true_value));
+            info!("Captured first calibration point: {} counts", raw);
+            set_status(CalibrationStatus::PointCaptured).await;
+            return;
+        }
+        CalibrationKind::SinglePoint => {
+            let offset = channel.correction_offset().await;
+            ((request.true_value - offset) / (raw * channel.adc_scale()), offset)
+        }
+        CalibrationKind::SecondPoint => {
+            let Some((_, raw1, true1)) = first_point.take() else {
+                return;
+            };
+            let delta = (raw - raw1) * channel.adc_scale();
+            if delta.abs() < CALIBRATION_MIN_RAW_COUNTS * channel.adc_scale() {
+                warn!("Calibration points too close: {} and {} counts", raw1, raw);
+                set_status(CalibrationStatus::NoSignal).await;
+                return;
+            }
+            let scale = (request.true_value - true1) / delta;
+            (scale, true1 - raw1 * channel.adc_scale() * scale)
+        }
+    };
+
+    let max_offset = CALIBRATION_MAX_OFFSET_FRACTION * channel.full_scale();
+    if !scale_in_bounds(correction_scale) || correction_offset.abs() > max_offset {
+        warn!(
+            "Calibration out of bounds: scale {}, offset {}",
+            correction_scale, correction_offset
+        );
+        let mut result = CALIBRATION_RESULT.lock().await;
+        result.status = CalibrationStatus::OutOfBounds;
+        result.correction_scale = correction_scale;
+        result.correction_offset = correction_offset;
+        return;
+    }
+
+    apply_calibration(channel, correction_scale, correction_offset, request.true_value).await;
+}
+
+#[task]
+pub async fn calibration_task() {
+    info!("Calibration task initialized");
+
+    let receiver = CALIBRATION_REQUEST_CHANNEL.receiver();
+    // Raw counts and true value of the first two-point calibration point
+    let mut first_point = None;
+
+    loop {
+        let request = receiver.receive().await;
+        run_calibration(request, &mut first_point).await;
+    }
+}
@@ -3,14 +3,17 @@ use embassy_executor::task;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel;
 use embassy_sync::mutex::Mutex;
+use embassy_sync::once_lock::OnceLock;
 use sequential_storage::cache::NoCache;
 use sequential_storage::map::{SerializationError, fetch_item, remove_item, store_item};
 use serde::{Deserialize, Serialize};
 
+use crate::analog_filter::FilterConfig;
 use crate::flash_layout::get_bootloader_appdata_range;
 use crate::{MFlashType, config::*};
 use crate::config_resources::ConfigManagerOutputResources;
-use crate::tasks::gpio_input::INPUTS;
+use crate::tasks::gpio_input::{INPUTS, notify_analog_config_changed};
+use crate::tasks::host_events::{HostEvent, post_host_event_with_payload};
 use embassy_rp::gpio::{Level, Output};
 
 // Define a comprehensive error type
@@ -52,6 +55,23 @@ pub enum ConfigManagerEvents {
     VscapCorrectionScale(f32),
     VinCorrectionScale(f32),
     IinCorrectionScale(f32),
+    VinCorrectionOffset(f32),
+    VscapCorrectionOffset(f32),
+    IinCorrectionOffset(f32),
+    VinFilterConfig(FilterConfig),
+    VscapFilterConfig(FilterConfig),
+    IinFilterConfig(FilterConfig),
+    Adc3Scale(f32),
+    Adc3Offset(f32),
+    HeaderGpioConfig(u8, u16),
+    AttentionConfig(u32),
+    LifetimeStats([u8; 32]),
+    TelemetryLogInterval(u16),
+    Schedule([u8; 32]),
+    ExplicitPingOnly(bool),
+    BootWatchdogTimeoutS(u16),
+    LivenessTimeoutMin(u16),
+    LivenessRecovery(u8),
     AutoRestart(bool),
     HardwareVersion(u32),
     UsbPortState(u8),
@@ -164,8 +184,19 @@ impl ConfigManager {
         Self { flash, data_buffer }
     }
 
-    /// Store a serializable value
+    /// Store a serializable value and report the write to the host
     pub async fn set<T>(&mut self, key: u16, value: &T) -> Result<(), ConfigError>
+    where
+        T: for<'de> Deserialize<'de> + Serialize + for<'b> sequential_storage::map::Value<'b>,
+    {
+        self.store(key, value).await?;
+        post_host_event_with_payload(HostEvent::ConfigWrite, key as u32).await;
+        Ok(())
+    }
+
+    /// Store a serializable value without reporting it to the host, for writes the
+    /// firmware makes on its own
+    async fn store<T>(&mut self, key: u16, value: &T) -> Result<(), ConfigError>
     where
         T: for<'de> Deserialize<'de> + Serialize + for<'b> sequential_storage::map::Value<'b>,
     {
@@ -293,6 +324,23 @@ struct RuntimeConfig {
     pub vin_correction_scale: f32,
     pub vscap_correction_scale: f32,
     pub iin_correction_scale: f32,
+    pub vin_correction_offset: f32,
+    pub vscap_correction_offset: f32,
+    pub iin_correction_offset: f32,
+    pub vin_filter_config: FilterConfig,
+    pub vscap_filter_config: FilterConfig,
+    pub iin_filter_config: FilterConfig,
+    pub adc3_scale: f32,
+    pub adc3_offset: f32,
+    pub header_gpio_configs: [u16; 3],
+    pub attention_config: u32,
+    pub lifetime_stats: [u8; 32],
+    pub telemetry_log_interval_s: u16,
+    pub schedule: [u8; 32],
+    pub explicit_ping_only: bool,
+    pub boot_watchdog_timeout_s: u16,
+    pub liveness_timeout_min: u16,
+    pub liveness_recovery: u8,
     pub auto_restart: bool,
     pub hardware_version: u32,
 }
@@ -310,6 +358,23 @@ impl RuntimeConfig {
         vin_correction_scale: f32,
         vscap_correction_scale: f32,
         iin_correction_scale: f32,
+        vin_correction_offset: f32,
+        vscap_correction_offset: f32,
+        iin_correction_offset: f32,
+        vin_filter_config: FilterConfig,
+        vscap_filter_config: FilterConfig,
+        iin_filter_config: FilterConfig,
+        adc3_scale: f32,
+        adc3_offset: f32,
+        header_gpio_configs: [u16; 3],
+        attention_config: u32,
+        lifetime_stats: [u8; 32],
+        telemetry_log_interval_s: u16,
+        schedule: [u8; 32],
+        explicit_ping_only: bool,
+        boot_watchdog_timeout_s: u16,
+        liveness_timeout_min: u16,
+        liveness_recovery: u8,
         auto_restart: bool,
         hardware_version: u32,
     ) -> Self {
@@ -324,6 +389,23 @@ impl RuntimeConfig {
             vin_correction_scale,
             vscap_correction_scale,
             iin_correction_scale,
+            vin_correction_offset,
+            vscap_correction_offset,
+            iin_correction_offset,
+            vin_filter_config,
+            vscap_filter_config,
+            iin_filter_config,
+            adc3_scale,
+            adc3_offset,
+            header_gpio_configs,
+            attention_config,
+            lifetime_stats,
+            telemetry_log_interval_s,
+            schedule,
+            explicit_ping_only,
+            boot_watchdog_timeout_s,
+            liveness_timeout_min,
+            liveness_recovery,
             auto_restart,
             hardware_version,
         }
@@ -342,10 +424,36 @@ static RUNTIME_CONFIG: Mutex<CriticalSectionRawMutex, RuntimeConfig> =
         DEFAULT_VIN_CORRECTION_SCALE,
         DEFAULT_VSCAP_CORRECTION_SCALE,
         DEFAULT_IIN_CORRECTION_SCALE,
+        DEFAULT_CORRECTION_OFFSET,
+        DEFAULT_CORRECTION_OFFSET,
+        DEFAULT_CORRECTION_OFFSET,
+        DEFAULT_ANALOG_FILTER,
+        DEFAULT_ANALOG_FILTER,
+        DEFAULT_ANALOG_FILTER,
+        DEFAULT_ADC3_SCALE,
+        DEFAULT_ADC3_OFFSET,
+        [DEFAULT_HEADER_GPIO_CONFIG; 3],
+        DEFAULT_ATTENTION_CONFIG,
+        [0; 32],
+        DEFAULT_TELEMETRY_LOG_INTERVAL_S,
+        [0; 32],
+        DEFAULT_EXPLICIT_PING_ONLY,
+        DEFAULT_BOOT_WATCHDOG_TIMEOUT_S,
+        DEFAULT_LIVENESS_TIMEOUT_MIN,
+        DEFAULT_LIVENESS_RECOVERY,
         DEFAULT_AUTO_RESTART,
         DEFAULT_HARDWARE_VERSION,
     ));
 
+/// Initialized once the stored configuration has been loaded into the runtime config
+static CONFIG_LOADED: OnceLock<()> = OnceLock::new();
+
+/// Wait until the stored configuration has been loaded. Tasks that apply configuration
+/// at startup must wait for this, otherwise they may see the defaults.
+pub async fn wait_for_config_loaded() {
+    CONFIG_LOADED.get().await;
+}
+
 pub async fn get_vscap_power_on_threshold() -> f32 {
     let config = RUNTIME_CONFIG.lock().await;
     config.vscap_power_on_threshold
@@ -368,7 +476,6 @@ pub async fn get_solo_depleting_timeout_ms() -> u32 {
     let config = RUNTIME_CONFIG.lock().await;
     config.solo_depleting_timeout_ms
 }
-#[allow(dead_code)]
 pub async fn get_watchdog_timeout_ms() -> u16 {
     let config = RUNTIME_CONFIG.lock().await;
     config.watchdog_timeout_ms
@@ -389,6 +496,74 @@ pub async fn get_iin_correction_scale() -> f32 {
     let config = RUNTIME_CONFIG.lock().await;
     config.iin_correction_scale
 }
+pub async fn get_vin_correction_offset() -> f32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.vin_correction_offset
+}
+pub async fn get_vscap_correction_offset() -> f32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.vscap_correction_offset
+}
+pub async fn get_iin_correction_offset() -> f32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.iin_correction_offset
+}
+pub async fn get_vin_filter_config() -> FilterConfig {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.vin_filter_config
+}
+pub async fn get_vscap_filter_config() -> FilterConfig {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.vscap_filter_config
+}
+pub async fn get_iin_filter_config() -> FilterConfig {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.iin_filter_config
+}
+pub async fn get_adc3_scale() -> f32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.adc3_scale
+}
+pub async fn get_adc3_offset() -> f32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.adc3_offset
+}
+pub async fn get_header_gpio_config(index: usize) -> u16 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.header_gpio_configs[index]
+}
+pub async fn get_attention_config() -> u32 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.attention_config
+}
+pub async fn get_lifetime_stats() -> [u8; 32] {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.lifetime_stats
+}
+pub async fn get_telemetry_log_interval() -> u16 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.telemetry_log_interval_s
+}
+pub async fn get_schedule() -> [u8; 32] {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.schedule
+}
+pub async fn get_explicit_ping_only() -> bool {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.explicit_ping_only
+}
+pub async fn get_boot_watchdog_timeout_s() -> u16 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.boot_watchdog_timeout_s
+}
+pub async fn get_liveness_timeout_min() -> u16 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.liveness_timeout_min
+}
+pub async fn get_liveness_recovery() -> u8 {
+    let config = RUNTIME_CONFIG.lock().await;
+    config.liveness_recovery
+}
 pub async fn get_auto_restart() -> bool {
     let config = RUNTIME_CONFIG.lock().await;
     config.auto_restart
@@ -434,7 +609,6 @@ pub async fn set_solo_depleting_timeout_ms(value: u32) {
         .send(ConfigManagerEvents::SoloDepletingTimeoutMs(value))
         .await;
 }
-#[allow(dead_code)]
 pub async fn set_watchdog_timeout_ms(value: u16) {
     let mut config = RUNTIME_CONFIG.lock().await;
     config.watchdog_timeout_ms = value;
@@ -455,6 +629,7 @@ pub async fn set_vin_correction_scale(value: f32) {
     CONFIG_MANAGER_EVENT_CHANNEL
         .send(ConfigManagerEvents::VinCorrectionScale(value))
         .await;
+    notify_analog_config_changed();
 }
 pub async fn set_vscap_correction_scale(value: f32) {
     let mut config = RUNTIME_CONFIG.lock().await;
@@ -462,6 +637,7 @@ pub async fn set_vscap_correction_scale(value: f32) {
     CONFIG_MANAGER_EVENT_CHANNEL
         .send(ConfigManagerEvents::VscapCorrectionScale(value))
         .await;
+    notify_analog_config_changed();
 }
 pub async fn set_iin_correction_scale(value: f32) {
     let mut config = RUNTIME_CONFIG.lock().await;
@@ -469,6 +645,134 @@ pub async fn set_iin_correction_scale(value: f32) {
     CONFIG_MANAGER_EVENT_CHANNEL
         .send(ConfigManagerEvents::IinCorrectionScale(value))
         .await;
+    notify_analog_config_changed();
+}
+pub async fn set_vin_correction_offset(value: f32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.vin_correction_offset = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::VinCorrectionOffset(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_vscap_correction_offset(value: f32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.vscap_correction_offset = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::VscapCorrectionOffset(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_iin_correction_offset(value: f32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.iin_correction_offset = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::IinCorrectionOffset(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_vin_filter_config(value: FilterConfig) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.vin_filter_config = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::VinFilterConfig(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_vscap_filter_config(value: FilterConfig) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.vscap_filter_config = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::VscapFilterConfig(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_iin_filter_config(value: FilterConfig) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.iin_filter_config = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::IinFilterConfig(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_adc3_scale(value: f32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.adc3_scale = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::Adc3Scale(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_adc3_offset(value: f32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.adc3_offset = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::Adc3Offset(value))
+        .await;
+    notify_analog_config_changed();
+}
+pub async fn set_header_gpio_config(index: usize, value: u16) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.header_gpio_configs[index] = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::HeaderGpioConfig(index as u8, value))
+        .await;
+}
+pub async fn set_attention_config(value: u32) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.attention_config = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::AttentionConfig(value))
+        .await;
+}
+pub async fn set_lifetime_stats(value: [u8; 32]) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.lifetime_stats = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::LifetimeStats(value))
+        .await;
+}
+pub async fn set_telemetry_log_interval(value: u16) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.telemetry_log_interval_s = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::TelemetryLogInterval(value))
+        .await;
+}
+pub async fn set_schedule(value: [u8; 32]) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.schedule = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::Schedule(value))
+        .await;
+}
+pub async fn set_explicit_ping_only(value: bool) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.explicit_ping_only = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::ExplicitPingOnly(value))
+        .await;
+}
+pub async fn set_boot_watchdog_timeout_s(value: u16) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.boot_watchdog_timeout_s = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::BootWatchdogTimeoutS(value))
+        .await;
+}
+pub async fn set_liveness_timeout_min(value: u16) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.liveness_timeout_min = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::LivenessTimeoutMin(value))
+        .await;
+}
+pub async fn set_liveness_recovery(value: u8) {
+    let mut config = RUNTIME_CONFIG.lock().await;
+    config.liveness_recovery = value;
+    CONFIG_MANAGER_EVENT_CHANNEL
+        .send(ConfigManagerEvents::LivenessRecovery(value))
+        .await;
 }
 pub async fn set_auto_restart(value: bool) {
     let mut config = RUNTIME_CONFIG.lock().await;
@@ -485,6 +789,39 @@ pub async fn set_hardware_version(value: u32) {
         .await;
 }
 
+/// Fetch a value stored under `key`. If it is missing, fall back to a value stored
+/// under `legacy_key` and copy it forward to `key`.
+async fn get_or_migrate_f32(
+    config_manager: &mut ConfigManager,
+    key: u16,
+    legacy_key: u16,
+    default: f32,
+) -> f32 {
+    if let Some(value) = config_manager.get::<f32>(key).await.unwrap_or(None) {
+        return value;
+    }
+    match config_manager.get::<f32>(legacy_key).await.unwrap_or(None) {
+        Some(value) => {
+            info!("Migrating config key 0x{:04x} to 0x{:04x}", legacy_key, key);
+            if config_manager.store(key, &value).await.is_err() {
+                error!("Failed to migrate config key 0x{:04x}", legacy_key);
+            }
+            value
+        }
+        None => default,
+    }
+}
+
+/// Fetch a packed filter config, falling back to the default if it is missing or invalid
+async fn get_filter_config(config_manager: &mut ConfigManager, key: u16) -> FilterConfig {
+    config_manager
+        .get::<u16>(key)
+        .await
+        .unwrap_or(None)
+        .and_then(FilterConfig::from_u16)
+        .unwrap_or(DEFAULT_ANALOG_FILTER)
+}
+
 pub async fn init_config_manager(
     flash: &'static MFlashType<'static>,
 ) -> Mutex<CriticalSectionRawMutex, ConfigManager> {
@@ -561,6 +898,122 @@ pub async fn init_config_manager(
             .unwrap_or(None)
             .unwrap_or(DEFAULT_HARDWARE_VERSION);
         debug!("Received hardware version: {}", hardware_version);
+        let vin_correction_scale = get_or_migrate_f32(
+            &mut config_manager,
+            VIN_CORRECTION_GAIN_CONFIG_KEY,
+            VIN_CORRECTION_SCALE_CONFIG_KEY,
+            DEFAULT_VIN_CORRECTION_SCALE,
+        )
+        .await;
+        debug!("Received vin correction scale: {}", vin_correction_scale);
+        let vscap_correction_scale = get_or_migrate_f32(
+            &mut config_manager,
+            VSCAP_CORRECTION_GAIN_CONFIG_KEY,
+            VSCAP_CORRECTION_SCALE_CONFIG_KEY,
+            DEFAULT_VSCAP_CORRECTION_SCALE,
+        )
+        .await;
+        debug!("Received vscap correction scale: {}", vscap_correction_scale);
+        let iin_correction_scale = get_or_migrate_f32(
+            &mut config_manager,
+            IIN_CORRECTION_GAIN_CONFIG_KEY,
+            IIN_CORRECTION_SCALE_CONFIG_KEY,
+            DEFAULT_IIN_CORRECTION_SCALE,
+        )
+        .await;
+        debug!("Received iin correction scale: {}", iin_correction_scale);
+        let vin_correction_offset = config_manager
+            .get::<f32>(VIN_CORRECTION_OFFSET_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_CORRECTION_OFFSET);
+        debug!("Received vin correction offset: {}", vin_correction_offset);
+        let vscap_correction_offset = config_manager
+            .get::<f32>(VSCAP_CORRECTION_OFFSET_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_CORRECTION_OFFSET);
+        debug!("Received vscap correction offset: {}", vscap_correction_offset);
+        let iin_correction_offset = config_manager
+            .get::<f32>(IIN_CORRECTION_OFFSET_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_CORRECTION_OFFSET);
+        debug!("Received iin correction offset: {}", iin_correction_offset);
+        let vin_filter_config = get_filter_config(&mut config_manager, VIN_FILTER_CONFIG_KEY).await;
+        debug!("Received vin filter config: {}", vin_filter_config);
+        let vscap_filter_config =
+            get_filter_config(&mut config_manager, VSCAP_FILTER_CONFIG_KEY).await;
+        debug!("Received vscap filter config: {}", vscap_filter_config);
+        let iin_filter_config = get_filter_config(&mut config_manager, IIN_FILTER_CONFIG_KEY).await;
+        debug!("Received iin filter config: {}", iin_filter_config);
+        let adc3_scale = config_manager
+            .get::<f32>(ADC3_SCALE_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_ADC3_SCALE);
+        debug!("Received adc3 scale: {}", adc3_scale);
+        let adc3_offset = config_manager
+            .get::<f32>(ADC3_OFFSET_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_ADC3_OFFSET);
+        debug!("Received adc3 offset: {}", adc3_offset);
+        let mut header_gpio_configs = [DEFAULT_HEADER_GPIO_CONFIG; 3];
+        for (config, key) in header_gpio_configs.iter_mut().zip(HEADER_GPIO_CONFIG_KEYS) {
+            *config = config_manager
+                .get::<u16>(key)
+                .await
+                .unwrap_or(None)
+                .unwrap_or(DEFAULT_HEADER_GPIO_CONFIG);
+        }
+        debug!("Received header gpio configs: {}", header_gpio_configs);
+        let attention_config = config_manager
+            .get::<u32>(ATTENTION_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_ATTENTION_CONFIG);
+        debug!("Received attention config: {}", attention_config);
+        let lifetime_stats = config_manager
+            .get::<[u8; 32]>(LIFETIME_STATS_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or([0; 32]);
+        let telemetry_log_interval_s = config_manager
+            .get::<u16>(TELEMETRY_LOG_INTERVAL_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_TELEMETRY_LOG_INTERVAL_S);
+        debug!("Received telemetry log interval: {}", telemetry_log_interval_s);
+        let schedule = config_manager
+            .get::<[u8; 32]>(SCHEDULE_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or([0; 32]);
+        let explicit_ping_only = config_manager
+            .get::<bool>(EXPLICIT_PING_ONLY_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_EXPLICIT_PING_ONLY);
+        debug!("Received explicit ping only: {}", explicit_ping_only);
+        let boot_watchdog_timeout_s = config_manager
+            .get::<u16>(BOOT_WATCHDOG_TIMEOUT_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_BOOT_WATCHDOG_TIMEOUT_S);
+        debug!("Received boot watchdog timeout: {}", boot_watchdog_timeout_s);
+        let liveness_timeout_min = config_manager
+            .get::<u16>(LIVENESS_TIMEOUT_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_LIVENESS_TIMEOUT_MIN);
+        debug!("Received liveness timeout: {}", liveness_timeout_min);
+        let liveness_recovery = config_manager
+            .get::<u8>(LIVENESS_RECOVERY_CONFIG_KEY)
+            .await
+            .unwrap_or(None)
+            .unwrap_or(DEFAULT_LIVENESS_RECOVERY);
+        debug!("Received liveness recovery: {}", liveness_recovery);
 
         let mut runtime_config = RUNTIME_CONFIG.lock().await;
         runtime_config.vscap_power_on_threshold = vscap_power_on_threshold;
@@ -572,6 +1025,27 @@ pub async fn init_config_manager(
         runtime_config.led_brightness = led_brightness;
         runtime_config.auto_restart = auto_restart;
         runtime_config.hardware_version = hardware_version;
+        runtime_config.vin_correction_scale = vin_correction_scale;
+        runtime_config.vscap_correction_scale = vscap_correction_scale;
+        runtime_config.iin_correction_scale = iin_correction_scale;
+        runtime_config.vin_correction_offset = vin_correction_offset;
+        runtime_config.vscap_correction_offset = vscap_correction_offset;
+        runtime_config.iin_correction_offset = iin_correction_offset;
+        runtime_config.vin_filter_config = vin_filter_config;
+        runtime_config.vscap_filter_config = vscap_filter_config;
+        runtime_config.iin_filter_config = iin_filter_config;
+        runtime_config.adc3_scale = adc3_scale;
+        runtime_config.adc3_offset = adc3_offset;
+        runtime_config.header_gpio_configs = header_gpio_configs;
+        runtime_config.attention_config = attention_config;
+        runtime_config.lifetime_stats = lifetime_stats;
+        runtime_config.telemetry_log_interval_s = telemetry_log_interval_s;
+        runtime_config.schedule = schedule;
+        runtime_config.explicit_ping_only = explicit_ping_only;
+        runtime_config.boot_watchdog_timeout_s = boot_watchdog_timeout_s;
+        runtime_config.liveness_timeout_min = liveness_timeout_min;
+        runtime_config.liveness_recovery = liveness_recovery;
+        let _ = CONFIG_LOADED.init(());
     }
     info!("Runtime configuration updated");
     config_manager_mutex
@@ -648,19 +1122,122 @@ pub async fn config_manager_task(flash: &'static MFlashType<'static>, usb_resour
             }
             ConfigManagerEvents::VinCorrectionScale(value) => {
                 config_manager
-                    .set(VIN_CORRECTION_SCALE_CONFIG_KEY, &value)
+                    .set(VIN_CORRECTION_GAIN_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::VinCorrectionOffset(value) => {
+                config_manager
+                    .set(VIN_CORRECTION_OFFSET_CONFIG_KEY, &value)
                     .await
                     .unwrap();
             }
             ConfigManagerEvents::VscapCorrectionScale(value) => {
                 config_manager
-                    .set(VSCAP_CORRECTION_SCALE_CONFIG_KEY, &value)
+                    .set(VSCAP_CORRECTION_GAIN_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::VscapCorrectionOffset(value) => {
+                config_manager
+                    .set(VSCAP_CORRECTION_OFFSET_CONFIG_KEY, &value)
                     .await
                     .unwrap();
             }
             ConfigManagerEvents::IinCorrectionScale(value) => {
                 config_manager
-                    .set(IIN_CORRECTION_SCALE_CONFIG_KEY, &value)
+                    .set(IIN_CORRECTION_GAIN_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::IinCorrectionOffset(value) => {
+                config_manager
+                    .set(IIN_CORRECTION_OFFSET_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::VinFilterConfig(value) => {
+                config_manager
+                    .set(VIN_FILTER_CONFIG_KEY, &value.to_u16())
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::VscapFilterConfig(value) => {
+                config_manager
+                    .set(VSCAP_FILTER_CONFIG_KEY, &value.to_u16())
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::IinFilterConfig(value) => {
+                config_manager
+                    .set(IIN_FILTER_CONFIG_KEY, &value.to_u16())
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::Adc3Scale(value) => {
+                config_manager
+                    .set(ADC3_SCALE_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::Adc3Offset(value) => {
+                config_manager
+                    .set(ADC3_OFFSET_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::HeaderGpioConfig(index, value) => {
+                config_manager
+                    .set(HEADER_GPIO_CONFIG_KEYS[index as usize], &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::AttentionConfig(value) => {
+                config_manager
+                    .set(ATTENTION_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::LifetimeStats(value) => {
+                // Periodic checkpoint, not a host configuration change
+                config_manager
+                    .store(LIFETIME_STATS_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::TelemetryLogInterval(value) => {
+                config_manager
+                    .set(TELEMETRY_LOG_INTERVAL_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::Schedule(value) => {
+                config_manager
+                    .set(SCHEDULE_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::ExplicitPingOnly(value) => {
+                config_manager
+                    .set(EXPLICIT_PING_ONLY_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::BootWatchdogTimeoutS(value) => {
+                config_manager
+                    .set(BOOT_WATCHDOG_TIMEOUT_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::LivenessTimeoutMin(value) => {
+                config_manager
+                    .set(LIVENESS_TIMEOUT_CONFIG_KEY, &value)
+                    .await
+                    .unwrap();
+            }
+            ConfigManagerEvents::LivenessRecovery(value) => {
+                config_manager
+                    .set(LIVENESS_RECOVERY_CONFIG_KEY, &value)
                     .await
                     .unwrap();
             }
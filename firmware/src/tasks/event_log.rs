@@ -0,0 +1,219 @@
+//! Persistent fault and event log.
+//!
+//! Shutdowns, alarms, resets and firmware updates are appended to a dedicated
+//! flash partition (EVENTLOG in memory.x) so that they survive resets and power
+//! loss. The partition is managed as a `sequential-storage` queue: writes are
+//! spread over all pages and the oldest records are overwritten once the
+//! partition is full. The host reads the log in chunks of records over I2C
+//! (register 0x88) and can erase it (register 0x89). The queue can only be
+//! walked from the oldest record, so a read stops as soon as its chunk is full
+//! and the record count is cached until the next write.
+
+use defmt::{debug, error, info, warn};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+use sequential_storage::cache::NoCache;
+use sequential_storage::{erase_all, queue};
+
+use crate::{MFlashType, OM_FLASH};
+use crate::config::{EVENT_LOG_CHANNEL_DEPTH, EVENT_LOG_CHUNK_RECORDS};
+use crate::crash_log::{CrashKind, get_reset_report};
+use crate::flash_layout::get_eventlog_range;
+use crate::rtc::rtc_epoch_s;
+
+/// Logged event kinds. The discriminant is stored in flash and is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum LogEventKind {
+    /// The controller firmware started. Payload: reset reason (see `ResetReason`)
+    Boot = 0,
+    /// The state machine entered a shutdown or powered-down state.
+    /// Payload: packed `ShutdownRecord` (reason, state, graceful flag)
+    Shutdown = 1,
+    /// External power was lost. Payload: supercap voltage in mV
+    Blackout = 2,
+    /// Supercap overvoltage alarm. Payload: supercap voltage in mV
+    SupercapAlarm = 3,
+    /// MCU temperature alarm. Payload: temperature in centidegrees Celsius
+    ThermalAlarm = 4,
+    /// The host watchdog expired
+    WatchdogTimeout = 5,
+    /// A firmware update was started. Payload: firmware size in bytes
+    DfuStarted = 6,
+    /// A firmware update was committed and will be applied at the next reset
+    DfuCommitted = 7,
+    /// A firmware update failed
+    DfuFailed = 8,
+    /// The previous run ended in a panic. Payload: source line of the panic
+    Panic = 9,
+    /// The previous run ended in a HardFault. Payload: faulting PC
+    HardFault = 10,
+    /// The CM5 looked hung in solo mode. Payload: cause (0 = LED_ACTIVE frozen,
+    /// 1 = LED_PWR blinking) in bits 8-15, recovery action in bits 0-7
+    LivenessLost = 11,
+    /// The CM5 bootloader flashed an error code. Payload: long flashes in bits 8-15,
+    /// short flashes in bits 0-7
+    BootError = 12,
+}
+
+#[derive(Clone, Copy, defmt::Format)]
+struct LogRecord {
+    kind: LogEventKind,
+    payload: u32,
+    /// Uptime in milliseconds
+    timestamp: u32,
+    /// UTC seconds since the Unix epoch, 0 if the clock was not set
+    wall_time: u32,
+}
+
+/// Size of a log record in flash and in the I2C wire format
+const LOG_RECORD_SIZE: usize = 13;
+
+impl LogRecord {
+    /// Stored format: kind, payload (u32 BE), uptime ms (u32 BE), UTC seconds (u32 BE)
+    fn to_bytes(self) -> [u8; LOG_RECORD_SIZE] {
+        let mut bytes = [0u8; LOG_RECORD_SIZE];
+        bytes[0] = self.kind as u8;
+        bytes[1..5].copy_from_slice(&self.payload.to_be_bytes());
+        bytes[5..9].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[9..13].copy_from_slice(&self.wall_time.to_be_bytes());
+        bytes
+    }
+}
+
+enum EventLogCommand {
+    Append(LogRecord),
+    Erase,
+}
+
+type EventLogChannelType =
+    channel::Channel<CriticalSectionRawMutex, EventLogCommand, EVENT_LOG_CHANNEL_DEPTH>;
+static EVENT_LOG_CHANNEL: EventLogChannelType = channel::Channel::new();
+
+/// Number of records in the log, or None if it has to be counted again
+static RECORD_COUNT: Mutex<CriticalSectionRawMutex, Option<u16>> = Mutex::new(None);
+
+/// Size of a log chunk response: total record count (u16 BE), number of records in
+/// the chunk, then the records
+pub const LOG_CHUNK_RESPONSE_SIZE: usize = 3 + EVENT_LOG_CHUNK_RECORDS * LOG_RECORD_SIZE;
+
+/// Append an event to the flash log. The write happens in the background; if the
+/// log task is busy and its queue is full, the event is dropped.
+pub fn log_event(kind: LogEventKind, payload: u32) {
+    let record = LogRecord {
+        kind,
+        payload,
+        timestamp: Instant::now().as_millis() as u32,
+        wall_time: rtc_epoch_s().unwrap_or(0),
+    };
+    if EVENT_LOG_CHANNEL
+        .try_send(EventLogCommand::Append(record))
+        .is_err()
+    {
+        warn!("Event log queue full, dropping {}", record);
+    }
+}
+
+/// Erase the whole event log
+pub async fn erase_event_log() {
+    EVENT_LOG_CHANNEL.send(EventLogCommand::Erase).await;
+}
+
+/// Read a chunk of records starting at record `start` (0 = oldest) in I2C wire format:
+/// total number of records (u16 BE), number of records in the chunk, then the records.
+/// The chunk is empty once `start` reaches the end of the log.
+pub async fn read_event_log_chunk(start: u16) -> [u8; LOG_CHUNK_RESPONSE_SIZE] {
+    let mut response = [0u8; LOG_CHUNK_RESPONSE_SIZE];
+    let mut buffer = [0u8; LOG_RECORD_SIZE];
+    let mut index = 0u16;
+    let mut returned = 0;
+    let mut count = *RECORD_COUNT.lock().await;
+
+    let mut flash = OM_FLASH.get().await.lock().await;
+    let mut cache = NoCache::new();
+    let mut iterator = match queue::iter(&mut *flash, get_eventlog_range(), &mut cache).await {
+        Ok(iterator) => iterator,
+        Err(e) => {
+            error!("Failed to read event log: {}", defmt::Debug2Format(&e));
+            return response;
+        }
+    };
+    loop {
+        // With a known record count, the rest of the log does not need to be read
+        if returned == EVENT_LOG_CHUNK_RECORDS && count.is_some() {
+            break;
+        }
+        match iterator.next(&mut buffer).await {
+            // Records in an older format are neither returned nor counted
+            Ok(Some(entry)) if entry.len() != LOG_RECORD_SIZE => {}
+            Ok(Some(entry)) => {
+                if index >= start && returned < EVENT_LOG_CHUNK_RECORDS {
+                    let offset = 3 + returned * LOG_RECORD_SIZE;
+                    response[offset..offset + LOG_RECORD_SIZE].copy_from_slice(entry);
+                    returned += 1;
+                }
+                index = index.saturating_add(1);
+            }
+            Ok(None) => {
+                count = Some(index);
+                *RECORD_COUNT.lock().await = count;
+                break;
+            }
+            Err(e) => {
+                error!("Failed to read event log: {}", defmt::Debug2Format(&e));
+                break;
+            }
+        }
+    }
+    response[0..2].copy_from_slice(&count.unwrap_or(index).to_be_bytes());
+    response[2] = returned as u8;
+    response
+}
+
+#[task]
+pub async fn event_log_task(flash: &'static MFlashType<'static>) {
+    info!("Starting event log task");
+
+    // Copy the outcome of the previous run to flash
+    let report = get_reset_report().await;
+    log_event(LogEventKind::Boot, report.reason as u32);
+    match report.crash_kind {
+        CrashKind::Panic => log_event(LogEventKind::Panic, report.pc),
+        CrashKind::HardFault => log_event(LogEventKind::HardFault, report.pc),
+        CrashKind::None => {}
+    }
+
+    let receiver = EVENT_LOG_CHANNEL.receiver();
+    loop {
+        match receiver.receive().await {
+            EventLogCommand::Append(record) => {
+                debug!("Logging {}", record);
+                let mut flash = flash.lock().await;
+                let result = queue::push(
+                    &mut *flash,
+                    get_eventlog_range(),
+                    &mut NoCache::new(),
+                    &record.to_bytes(),
+                    true,
+                )
+                .await;
+                if let Err(e) = result {
+                    error!("Failed to write event log: {}", defmt::Debug2Format(&e));
+                }
+                // Old records may have been overwritten, count again on the next read
+                *RECORD_COUNT.lock().await = None;
+            }
+            EventLogCommand::Erase => {
+                info!("Erasing event log");
+                let mut flash = flash.lock().await;
+                if let Err(e) = erase_all(&mut *flash, get_eventlog_range()).await {
+                    error!("Failed to erase event log: {}", defmt::Debug2Format(&e));
+                }
+                *RECORD_COUNT.lock().await = None;
+            }
+        }
+    }
+}
@@ -14,6 +14,7 @@ use embassy_time::Timer;
 // Import the NorFlash trait for async write support
 use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
 
+use crate::tasks::event_log::{LogEventKind, log_event};
 use crate::{
     MFlashType,
     config::{FLASH_ERASE_BLOCK_SIZE, FLASH_WRITE_BLOCK_SIZE, MAX_FLASH_WRITE_QUEUE_DEPTH},
@@ -165,6 +166,7 @@ async fn start_update(flash: &MFlashType<'static>, total_size: u32, num_blocks:
         status.total_num_blocks = num_blocks;
         status.error_details = None;
     }
+    log_event(LogEventKind::DfuStarted, total_size);
     match prepare_update(flash).await {
         Ok(_) => {
             defmt::debug!("update prepared");
@@ -173,6 +175,7 @@ async fn start_update(flash: &MFlashType<'static>, total_size: u32, num_blocks:
         }
         Err(e) => {
             defmt::error!("E: {:?}", defmt::Debug2Format(&e));
+            log_event(LogEventKind::DfuFailed, 0);
             let mut status = { FLASH_WRITER_STATUS.lock().await };
             status.state = FlashUpdateState::WriteError;
             status.error_details = Some(e);
@@ -197,6 +200,7 @@ async fn commit_update(flash: &MFlashType<'static>) {
     match firmware_updater.mark_updated().await {
         Ok(_) => {
             defmt::debug!("update committed");
+            log_event(LogEventKind::DfuCommitted, 0);
             let mut status = FLASH_WRITER_STATUS.lock().await;
             status.state = FlashUpdateState::Complete;
             status.blocks_received = 0;
@@ -207,6 +211,7 @@ async fn commit_update(flash: &MFlashType<'static>) {
         }
         Err(e) => {
             defmt::warn!("E: {:?}", defmt::Debug2Format(&e));
+            log_event(LogEventKind::DfuFailed, 0);
             let mut status = FLASH_WRITER_STATUS.lock().await;
             status.state = FlashUpdateState::WriteError;
             status.error_details = Some(String::from("Failed to commit update"));
@@ -273,6 +278,7 @@ pub async fn flash_writer_task(flash: &'static MFlashType<'static>) {
                             }
                             Err(e) => {
                                 // Handle write error
+                                log_event(LogEventKind::DfuFailed, 0);
                                 let mut status = FLASH_WRITER_STATUS.lock().await;
                                 status.state = FlashUpdateState::WriteError;
                                 status.error_details = Some(e);
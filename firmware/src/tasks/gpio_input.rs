@@ -5,15 +5,23 @@ use embassy_rp::{
     bind_interrupts,
     gpio::{Input, Pull},
 };
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embassy_time::{Duration, Ticker};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Instant, Ticker};
 
 use crate::{
-    config::{VIN_MAX_VALUE, VSCAP_MAX_VALUE}, config_resources::{AnalogInputResources, DigitalInputResources, PowerButtonInputResources, TestModeResources, UserButtonInputResources}, tasks::power_button::POWER_BUTTON_EVENT_CHANNEL
+    analog_filter::{Filter, FilterConfig, FilterType},
+    config::{DEFAULT_VIN_POWER_THRESHOLD, VIN_FAST_DROP_BURSTS, VIN_MAX_VALUE, VSCAP_MAX_VALUE, WAVEFORM_SAMPLE_INTERVAL_US}, config_resources::{AnalogInputResources, DigitalInputResources, PowerButtonInputResources, TestModeResources, UserButtonInputResources}, tasks::power_button::POWER_BUTTON_EVENT_CHANNEL
 };
 
 use super::power_button::{PowerButtonEvents};
 use crate::tasks::state_machine::{STATE_MACHINE_EVENT_CHANNEL, StateMachineEvents};
+use crate::tasks::boot_code::record_boot_code_sample;
+use crate::tasks::config_manager::wait_for_config_loaded;
+use crate::tasks::host_events::{HostEvent, post_host_event};
+use crate::tasks::liveness::record_led_sample;
+use crate::tasks::shutdown_reason::note_power_button_press;
+use crate::tasks::test_mode::set_test_mode_requested;
+use crate::tasks::waveform::record_waveform_sample;
 
 /// Input values that are read by the io_task and consumed by other tasks.
 #[derive(Clone, Format)]
@@ -21,6 +29,12 @@ pub struct Inputs {
     pub vin: f32,
     pub vscap: f32,
     pub iin: f32,
+    pub vin_raw: u16,
+    pub vscap_raw: u16,
+    pub iin_raw: u16,
+    /// User analog input on GPIO29_ADC3, scaled to the configured unit
+    pub adc3: f32,
+    pub adc3_raw: u16,
     pub cm_on: bool,
     pub mcu_temp: f32,
     pub pcb_temp: f32,
@@ -38,6 +52,11 @@ impl Inputs {
             vin: 0.,
             vscap: 0.,
             iin: 0.,
+            vin_raw: 0,
+            vscap_raw: 0,
+            iin_raw: 0,
+            adc3: 0.,
+            adc3_raw: 0,
             cm_on: false,
             mcu_temp: 0.,
             pcb_temp: 0.,
@@ -54,45 +73,12 @@ impl Inputs {
 /// Shared inputs protected by a mutex.
 pub static INPUTS: Mutex<CriticalSectionRawMutex, Inputs> = Mutex::new(Inputs::new());
 
-const AVERAGE_SAMPLES: usize = 10;
+/// Signalled when analog calibration parameters change. The analog input task
+/// reloads them before the next sample.
+static ANALOG_CONFIG_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
-struct AveragedInput {
-    samples: [f32; AVERAGE_SAMPLES],
-    index: usize,
-    sum: f32,
-    count: usize,
-}
-
-impl AveragedInput {
-    fn new() -> Self {
-        Self {
-            samples: [0.0; AVERAGE_SAMPLES],
-            index: 0,
-            sum: 0.0,
-            count: 0,
-        }
-    }
-
-    fn add_sample(&mut self, value: f32) {
-        if self.count < AVERAGE_SAMPLES {
-            self.samples[self.index] = value;
-            self.sum += value;
-            self.count += 1;
-        } else {
-            self.sum -= self.samples[self.index];
-            self.samples[self.index] = value;
-            self.sum += value;
-        }
-        self.index = (self.index + 1) % AVERAGE_SAMPLES;
-    }
-
-    fn average(&self) -> f32 {
-        if self.count == 0 {
-            0.0
-        } else {
-            self.sum / self.count as f32
-        }
-    }
+pub fn notify_analog_config_changed() {
+    ANALOG_CONFIG_CHANGED.signal(());
 }
 
 #[task]
@@ -118,6 +104,8 @@ pub async fn power_button_input_task(
             POWER_BUTTON_EVENT_CHANNEL.send(PowerButtonEvents::Press).await;
             // Also send wake-up event to state machine for systems in off state
             STATE_MACHINE_EVENT_CHANNEL.send(StateMachineEvents::PowerButtonPress).await;
+            note_power_button_press().await;
+            post_host_event(HostEvent::PowerButton).await;
         }
     }
 }
@@ -135,6 +123,10 @@ pub async fn user_button_input_task(r: UserButtonInputResources) {
         let mut inputs = INPUTS.lock().await;
         // Update the user button input state
         inputs.user_btn = button.is_high();
+        drop(inputs);
+        if button.is_low() {
+            post_host_event(HostEvent::UserButton).await;
+        }
     }
 }
 
@@ -144,13 +136,24 @@ pub async fn test_mode_input_task(r: TestModeResources) {
 
     let mut test_pin = Input::new(r.pin, Pull::Up);
 
+    // TEST_MODE is active low. Factory test mode is only entered if the pin
+    // is asserted at boot.
+    {
+        let mut inputs = INPUTS.lock().await;
+        inputs.test_mode = test_pin.is_low();
+        if inputs.test_mode {
+            info!("Test mode pin asserted at boot");
+            set_test_mode_requested();
+        }
+    }
+
     info!("Test mode input task initialized");
 
     loop {
         test_pin.wait_for_any_edge().await;
         let mut inputs = INPUTS.lock().await;
         // Update the test mode input state
-        inputs.test_mode = test_pin.is_high();
+        inputs.test_mode = test_pin.is_low();
         info!("Test mode state changed: {}", inputs.test_mode);
     }
 }
@@ -182,12 +185,79 @@ pub async fn digital_input_task(r: DigitalInputResources) {
             "LED_PWR: {}, LED_ACTIVE: {}, PG_5V: {}, CM_ON: {}",
             inputs.led_pwr, inputs.led_active, inputs.pg_5v, inputs.cm_on
         );
+        record_led_sample(inputs.led_pwr, inputs.led_active).await;
+        record_boot_code_sample(inputs.led_active).await;
+    }
+}
+
+pub const VIN_ADC_SCALE: f32 = VIN_MAX_VALUE / 4096.0; // Scale factor for Vin readings
+pub const VSCAP_ADC_SCALE: f32 = VSCAP_MAX_VALUE / 4096.0; // Scale factor for Vscap readings
+pub const IIN_ADC_SCALE: f32 = 3.3 / 4096.0;
+pub const ADC3_ADC_SCALE: f32 = 3.3 / 4096.0; // Pin voltage per count
+
+/// Filter used for the MCU temperature and ADC3, which are not configurable
+const AUX_FILTER: FilterConfig = FilterConfig::new(FilterType::MovingAverage, 10);
+
+/// Calibration and filter parameters applied by the analog input task
+#[derive(Clone, Copy)]
+struct AnalogCalibration {
+    vin_adc_scale: f32,
+    vscap_adc_scale: f32,
+    iin_adc_scale: f32,
+    vin_offset: f32,
+    vscap_offset: f32,
+    iin_offset: f32,
+    vin_filter: FilterConfig,
+    vscap_filter: FilterConfig,
+    iin_filter: FilterConfig,
+    adc3_scale: f32,
+    adc3_offset: f32,
+}
+
+impl AnalogCalibration {
+    async fn load() -> Self {
+        use crate::tasks::config_manager::*;
+        Self {
+            vin_adc_scale: get_vin_correction_scale().await * VIN_ADC_SCALE,
+            vscap_adc_scale: get_vscap_correction_scale().await * VSCAP_ADC_SCALE,
+            iin_adc_scale: get_iin_correction_scale().await * IIN_ADC_SCALE,
+            vin_offset: get_vin_correction_offset().await,
+            vscap_offset: get_vscap_correction_offset().await,
+            iin_offset: get_iin_correction_offset().await,
+            vin_filter: get_vin_filter_config().await,
+            vscap_filter: get_vscap_filter_config().await,
+            iin_filter: get_iin_filter_config().await,
+            adc3_scale: get_adc3_scale().await * ADC3_ADC_SCALE,
+            adc3_offset: get_adc3_offset().await,
+        }
     }
 }
 
-const VIN_ADC_SCALE: f32 = VIN_MAX_VALUE / 4096.0; // Scale factor for Vin readings
-const VSCAP_ADC_SCALE: f32 = VSCAP_MAX_VALUE / 4096.0; // Scale factor for Vscap readings
-const IIN_ADC_SCALE: f32 = 3.3 / 4096.0;
+/// Number of ADC inputs sampled round-robin: VIN, VSCAP, IIN, ADC3 and the temperature sensor
+const ADC_CHANNELS: usize = 5;
+/// Readings per input in one DMA burst
+const ADC_BURST_ROUNDS: usize = 8;
+/// ADC clock divider: 48 MHz / (ADC_CLOCK_DIV + 1) = 100 kS/s shared by all inputs,
+/// so a burst takes 0.4 ms
+const ADC_CLOCK_DIV: u16 = 479;
+/// Interval at which filtered readings are published
+const ANALOG_SAMPLE_INTERVAL_MS: u64 = 20;
+
+/// Burst pre-filter. Oversampling filters average the last N bursts; the other
+/// filter types use the latest burst only.
+fn burst_filter(config: FilterConfig) -> Filter {
+    let window = match config.filter_type {
+        FilterType::Oversample => config.window,
+        _ => 1,
+    };
+    Filter::new(FilterConfig::new(FilterType::MovingAverage, window))
+}
+
+/// Average raw reading of one input in a round-robin burst buffer
+fn burst_mean(buf: &[u16], input: usize) -> f32 {
+    let sum: u32 = buf.iter().skip(input).step_by(ADC_CHANNELS).map(|&v| v as u32).sum();
+    sum as f32 / ADC_BURST_ROUNDS as f32
+}
 
 bind_interrupts!(struct Irqs {
     ADC_IRQ_FIFO => InterruptHandler;
@@ -198,48 +268,130 @@ pub async fn analog_input_task(r: AnalogInputResources) {
     info!("Starting analog input task");
     // Initialize the peripherals and GPIO pins
     let mut adc = Adc::new(r.adc, Irqs, Config::default());
-    let mut vins = Channel::new_pin(r.vin_s, Pull::None);
-    let mut vscaps = Channel::new_pin(r.vscap_s, Pull::None);
-    let mut iin = Channel::new_pin(r.iin, Pull::None);
-    let mut mcu_temp = Channel::new_temp_sensor(r.temp_sensor);
-
-    let mut ticker = Ticker::every(Duration::from_millis(20));
+    let mut dma = r.dma_ch;
+    let mut channels = [
+        Channel::new_pin(r.vin_s, Pull::None),
+        Channel::new_pin(r.vscap_s, Pull::None),
+        Channel::new_pin(r.iin, Pull::None),
+        Channel::new_pin(r.gpio29_adc3, Pull::None),
+        Channel::new_temp_sensor(r.temp_sensor),
+    ];
+    let mut buf = [0u16; ADC_CHANNELS * ADC_BURST_ROUNDS];
 
     info!("Analog input task initialized");
 
-    let mut vin_avg = AveragedInput::new();
-    let mut vscap_avg = AveragedInput::new();
-    let mut iin_avg = AveragedInput::new();
-    let mut mcu_temp_avg = AveragedInput::new();
+    wait_for_config_loaded().await;
+    let mut calibration = AnalogCalibration::load().await;
+
+    let mut vin_burst = burst_filter(calibration.vin_filter);
+    let mut vscap_burst = burst_filter(calibration.vscap_filter);
+    let mut iin_burst = burst_filter(calibration.iin_filter);
+    let mut vin_avg = Filter::new(calibration.vin_filter);
+    let mut vscap_avg = Filter::new(calibration.vscap_filter);
+    let mut iin_avg = Filter::new(calibration.iin_filter);
+    let mut adc3_avg = Filter::new(AUX_FILTER);
+    let mut mcu_temp_avg = Filter::new(AUX_FILTER);
 
-    let vin_adc_scale = crate::tasks::config_manager::get_vin_correction_scale().await * VIN_ADC_SCALE;
-    let vscap_adc_scale = crate::tasks::config_manager::get_vscap_correction_scale().await * VSCAP_ADC_SCALE;
-    let iin_adc_scale = crate::tasks::config_manager::get_iin_correction_scale().await * IIN_ADC_SCALE;
+    let mut next_sample = Instant::now();
+    let mut next_waveform_sample = Instant::now();
+    let mut vin_low_bursts = 0u32;
+    let mut vin_lost = false;
 
     loop {
-        ticker.next().await;
+        if ANALOG_CONFIG_CHANGED.try_take().is_some() {
+            calibration = AnalogCalibration::load().await;
+            // Samples taken with the old calibration would skew the averages
+            vin_burst = burst_filter(calibration.vin_filter);
+            vscap_burst = burst_filter(calibration.vscap_filter);
+            iin_burst = burst_filter(calibration.iin_filter);
+            vin_avg = Filter::new(calibration.vin_filter);
+            vscap_avg = Filter::new(calibration.vscap_filter);
+            iin_avg = Filter::new(calibration.iin_filter);
+            adc3_avg = Filter::new(AUX_FILTER);
+            info!("Analog calibration reloaded");
+        }
+
+        // Free-running round-robin conversion of all inputs, transferred by DMA
+        if let Err(e) = adc
+            .read_many_multichannel(&mut channels, &mut buf, false, ADC_CLOCK_DIV, dma.reborrow())
+            .await
+        {
+            warn!("ADC burst failed: {}", e);
+            continue;
+        }
+
+        let vin_mean = burst_mean(&buf, 0);
+        let vscap_mean = burst_mean(&buf, 1);
+        vin_burst.add_sample(vin_mean);
+        vscap_burst.add_sample(vscap_mean);
+        iin_burst.add_sample(burst_mean(&buf, 2));
+
+        // Fast VIN loss path. A backfeeding supercap holds VIN up to about VSCAP,
+        // so VIN below VSCAP also counts as lost.
+        let vin_now = vin_mean * calibration.vin_adc_scale + calibration.vin_offset;
+        let vscap_now = vscap_mean * calibration.vscap_adc_scale + calibration.vscap_offset;
+        if vin_now <= DEFAULT_VIN_POWER_THRESHOLD || vin_now < vscap_now {
+            vin_low_bursts = vin_low_bursts.saturating_add(1);
+        } else {
+            vin_low_bursts = 0;
+            vin_lost = false;
+        }
+        if vin_low_bursts >= VIN_FAST_DROP_BURSTS && !vin_lost {
+            vin_lost = true;
+            // Restart the VIN average so the published value reflects the loss at once
+            vin_avg = Filter::new(calibration.vin_filter);
+            vin_avg.add_sample(0.0);
+            INPUTS.lock().await.vin = 0.0;
+            if STATE_MACHINE_EVENT_CHANNEL
+                .try_send(StateMachineEvents::VinLost)
+                .is_err()
+            {
+                warn!("State machine event channel full, VIN loss left to polling");
+            }
+            info!("VIN loss detected: {} V", vin_now);
+        }
+
+        // Unfiltered samples for the power-loss waveform capture
+        if Instant::now() >= next_waveform_sample {
+            let iin_now = burst_mean(&buf, 2) * calibration.iin_adc_scale + calibration.iin_offset;
+            record_waveform_sample(vin_now, vscap_now, iin_now).await;
+            next_waveform_sample += Duration::from_micros(WAVEFORM_SAMPLE_INTERVAL_US as u64);
+            // Do not try to catch up after a stall
+            next_waveform_sample = next_waveform_sample.max(Instant::now());
+        }
+
+        if Instant::now() < next_sample {
+            continue;
+        }
+        next_sample += Duration::from_millis(ANALOG_SAMPLE_INTERVAL_MS);
 
-        trace!("Reading analog inputs");
+        trace!("Publishing analog inputs");
 
-        let vin = adc.read(&mut vins).await.unwrap_or(0);
-        let vscap_value = adc.read(&mut vscaps).await.unwrap_or(0);
-        let iin_value = adc.read(&mut iin).await.unwrap_or(0);
-        let mcu_temp_value = adc.read(&mut mcu_temp).await.unwrap_or(0);
+        let vin = vin_burst.value();
+        let vscap_value = vscap_burst.value();
+        let iin_value = iin_burst.value();
+        let adc3_value = burst_mean(&buf, 3);
+        let mcu_temp_value = burst_mean(&buf, 4);
 
-        let vin_sample = (vin as f32) * vin_adc_scale;
-        let vscap_sample = (vscap_value as f32) * vscap_adc_scale;
-        let iin_sample = (iin_value as f32) * iin_adc_scale;
-        let mcu_temp_sample =
-            27.0 - (mcu_temp_value as f32 * 3.3 / 4096.0 - 0.706) / 0.001721 + 273.15;
+        let vin_sample = vin * calibration.vin_adc_scale + calibration.vin_offset;
+        let vscap_sample = vscap_value * calibration.vscap_adc_scale + calibration.vscap_offset;
+        let iin_sample = iin_value * calibration.iin_adc_scale + calibration.iin_offset;
+        let adc3_sample = adc3_value * calibration.adc3_scale + calibration.adc3_offset;
+        let mcu_temp_sample = 27.0 - (mcu_temp_value * 3.3 / 4096.0 - 0.706) / 0.001721 + 273.15;
 
-        vin_avg.add_sample(vin_sample);
+        vin_avg.add_sample(if vin_lost { 0.0 } else { vin_sample });
         vscap_avg.add_sample(vscap_sample);
         iin_avg.add_sample(iin_sample);
+        adc3_avg.add_sample(adc3_sample);
         mcu_temp_avg.add_sample(mcu_temp_sample);
 
         let mut inputs = INPUTS.lock().await;
-        inputs.vin = vin_avg.average();
-        inputs.vscap = vscap_avg.average();
+        inputs.vin_raw = vin as u16;
+        inputs.vscap_raw = vscap_value as u16;
+        inputs.iin_raw = iin_value as u16;
+        inputs.adc3_raw = adc3_value as u16;
+        inputs.vin = vin_avg.value();
+        inputs.vscap = vscap_avg.value();
 
         if inputs.vin < inputs.vscap {
             // If Vin is less than Vscap, Vscap is backfeeding into Vin.
@@ -247,8 +399,9 @@ pub async fn analog_input_task(r: AnalogInputResources) {
             inputs.vin = 0.0;
         }
 
-        inputs.iin = iin_avg.average();
-        inputs.mcu_temp = mcu_temp_avg.average();
+        inputs.iin = iin_avg.value();
+        inputs.adc3 = adc3_avg.value();
+        inputs.mcu_temp = mcu_temp_avg.value();
 
         trace!(
             "VIN: {}, VSCAP: {}, IIN: {}",
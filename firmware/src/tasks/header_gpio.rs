@@ -0,0 +1,294 @@
+//! Host-controllable general-purpose I/O on the header pins GPIO06-GPIO08.
+//!
+//! Each pin can be configured over I2C as a floating, pulled-up or pulled-down
+//! input, a push-pull output or an open-drain output. Inputs can latch rising
+//! and/or falling edges for the host to collect. Pin configurations are stored
+//! in flash and applied at boot, before the CM5 is powered.
+//!
+//! One pin can instead be assigned as the host attention output (see
+//! `tasks::host_events`). While assigned, the host GPIO config of that pin is
+//! stored but not applied.
+
+use defmt::{debug, info};
+use embassy_executor::task;
+use embassy_rp::gpio::{Flex, Level, Pull};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Ticker};
+
+use crate::config::HEADER_GPIO_POLL_INTERVAL_MS;
+use crate::config_resources::HeaderGpioResources;
+use crate::tasks::config_manager::{
+    get_header_gpio_config, set_header_gpio_config, wait_for_config_loaded,
+};
+use crate::tasks::host_events::{
+    HostEvent, attention_output, init_host_events, post_host_event_with_payload,
+};
+
+pub const NUM_HEADER_GPIOS: usize = 3;
+
+/// Edge flags of a pin configuration
+pub const GPIO_EDGE_RISING: u8 = 0x01;
+pub const GPIO_EDGE_FALLING: u8 = 0x02;
+/// Output level applied when the pin is configured (and at boot)
+pub const GPIO_FLAG_LEVEL_HIGH: u8 = 0x80;
+const GPIO_FLAGS_MASK: u8 = GPIO_EDGE_RISING | GPIO_EDGE_FALLING | GPIO_FLAG_LEVEL_HIGH;
+
+/// Pin modes. The discriminant is part of the I2C API and the config format.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum GpioMode {
+    Input = 0,
+    InputPullUp = 1,
+    InputPullDown = 2,
+    Output = 3,
+    /// Driven low, or released (high impedance) for a high level
+    OpenDrain = 4,
+}
+
+impl GpioMode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Input),
+            1 => Some(Self::InputPullUp),
+            2 => Some(Self::InputPullDown),
+            3 => Some(Self::Output),
+            4 => Some(Self::OpenDrain),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct GpioPinConfig {
+    pub mode: GpioMode,
+    /// Edge capture and initial level flags
+    pub flags: u8,
+}
+
+impl GpioPinConfig {
+    pub const fn new(mode: GpioMode, flags: u8) -> Self {
+        Self { mode, flags }
+    }
+
+    /// Validate and build a pin config
+    pub fn from_parts(mode: u8, flags: u8) -> Option<Self> {
+        let mode = GpioMode::from_u8(mode)?;
+        if flags & !GPIO_FLAGS_MASK != 0 {
+            return None;
+        }
+        Some(Self::new(mode, flags))
+    }
+
+    /// Packed representation stored in the config: mode in the high byte, flags in the low byte
+    pub const fn to_u16(self) -> u16 {
+        ((self.mode as u16) << 8) | self.flags as u16
+    }
+
+    pub fn from_u16(value: u16) -> Option<Self> {
+        Self::from_parts((value >> 8) as u8, value as u8)
+    }
+
+    fn initial_level(&self) -> bool {
+        self.flags & GPIO_FLAG_LEVEL_HIGH != 0
+    }
+}
+
+enum HeaderGpioCommand {
+    Configure(usize, GpioPinConfig),
+    SetLevels { mask: u8, levels: u8 },
+}
+
+type HeaderGpioChannelType = channel::Channel<CriticalSectionRawMutex, HeaderGpioCommand, 4>;
+static HEADER_GPIO_COMMAND_CHANNEL: HeaderGpioChannelType = channel::Channel::new();
+
+struct HeaderGpioState {
+    configs: [GpioPinConfig; NUM_HEADER_GPIOS],
+    /// Current pin levels, bit N = GPIO0(6+N)
+    levels: u8,
+    /// Latched edge events, cleared when read by the host
+    edge_events: u8,
+}
+
+static HEADER_GPIO_STATE: Mutex<CriticalSectionRawMutex, HeaderGpioState> =
+    Mutex::new(HeaderGpioState {
+        configs: [GpioPinConfig::new(GpioMode::Input, 0); NUM_HEADER_GPIOS],
+        levels: 0,
+        edge_events: 0,
+    });
+
+/// Configure a pin and persist the configuration
+pub async fn configure_gpio(pin: usize, config: GpioPinConfig) {
+    set_header_gpio_config(pin, config.to_u16()).await;
+    HEADER_GPIO_COMMAND_CHANNEL
+        .send(HeaderGpioCommand::Configure(pin, config))
+        .await;
+}
+
+/// Set the levels of the output pins selected by `mask`. Input pins are ignored.
+pub async fn set_gpio_levels(mask: u8, levels: u8) {
+    HEADER_GPIO_COMMAND_CHANNEL
+        .send(HeaderGpioCommand::SetLevels { mask, levels })
+        .await;
+}
+
+/// Pin configurations in I2C wire format: mode and flags for each pin
+pub async fn get_gpio_configs() -> [u8; 2 * NUM_HEADER_GPIOS] {
+    let state = HEADER_GPIO_STATE.lock().await;
+    let mut bytes = [0u8; 2 * NUM_HEADER_GPIOS];
+    for (i, config) in state.configs.iter().enumerate() {
+        bytes[2 * i] = config.mode as u8;
+        bytes[2 * i + 1] = config.flags;
+    }
+    bytes
+}
+
+pub async fn get_gpio_levels() -> u8 {
+    HEADER_GPIO_STATE.lock().await.levels
+}
+
+/// Return and clear the latched edge events
+pub async fn take_gpio_edge_events() -> u8 {
+    let mut state = HEADER_GPIO_STATE.lock().await;
+    core::mem::take(&mut state.edge_events)
+}
+
+fn apply_level(pin: &mut Flex<'static>, mode: GpioMode, high: bool) {
+    match mode {
+        GpioMode::Output => pin.set_level(Level::from(high)),
+        GpioMode::OpenDrain => {
+            if high {
+                pin.set_as_input();
+            } else {
+                pin.set_low();
+                pin.set_as_output();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn apply_config(pin: &mut Flex<'static>, config: GpioPinConfig) {
+    match config.mode {
+        GpioMode::Input | GpioMode::InputPullUp | GpioMode::InputPullDown => {
+            pin.set_as_input();
+            pin.set_pull(match config.mode {
+                GpioMode::InputPullUp => Pull::Up,
+                GpioMode::InputPullDown => Pull::Down,
+                _ => Pull::None,
+            });
+        }
+        GpioMode::Output => {
+            pin.set_pull(Pull::None);
+            pin.set_level(Level::from(config.initial_level()));
+            pin.set_as_output();
+        }
+        GpioMode::OpenDrain => {
+            pin.set_pull(Pull::None);
+            apply_level(pin, config.mode, config.initial_level());
+        }
+    }
+}
+
+fn read_levels(pins: &[Flex<'static>; NUM_HEADER_GPIOS]) -> u8 {
+    pins.iter()
+        .enumerate()
+        .fold(0, |levels, (i, pin)| levels | ((pin.is_high() as u8) << i))
+}
+
+#[task]
+pub async fn header_gpio_task(r: HeaderGpioResources) {
+    info!("Starting header GPIO task");
+
+    let mut pins = [Flex::new(r.gpio06), Flex::new(r.gpio07), Flex::new(r.gpio08)];
+
+    wait_for_config_loaded().await;
+    init_host_events().await;
+    let mut configs = [GpioPinConfig::new(GpioMode::Input, 0); NUM_HEADER_GPIOS];
+    for (i, pin) in pins.iter_mut().enumerate() {
+        configs[i] = GpioPinConfig::from_u16(get_header_gpio_config(i).await)
+            .unwrap_or(GpioPinConfig::new(GpioMode::Input, 0));
+        apply_config(pin, configs[i]);
+    }
+    let mut prev_levels = read_levels(&pins);
+    {
+        let mut state = HEADER_GPIO_STATE.lock().await;
+        state.configs = configs;
+        state.levels = prev_levels;
+    }
+
+    let mut ticker = Ticker::every(Duration::from_millis(HEADER_GPIO_POLL_INTERVAL_MS as u64));
+    let receiver = HEADER_GPIO_COMMAND_CHANNEL.receiver();
+    let mut attention_pin: Option<usize> = None;
+
+    info!("Header GPIO task initialized");
+
+    loop {
+        ticker.next().await;
+
+        while let Ok(command) = receiver.try_receive() {
+            match command {
+                HeaderGpioCommand::Configure(i, config) => {
+                    debug!("Configuring GPIO0{}: {}", 6 + i, config);
+                    configs[i] = config;
+                    if attention_pin != Some(i) {
+                        apply_config(&mut pins[i], config);
+                    }
+                    HEADER_GPIO_STATE.lock().await.configs = configs;
+                }
+                HeaderGpioCommand::SetLevels { mask, levels } => {
+                    for (i, pin) in pins.iter_mut().enumerate() {
+                        if mask & (1 << i) != 0 && attention_pin != Some(i) {
+                            apply_level(pin, configs[i].mode, levels & (1 << i) != 0);
+                        }
+                    }
+                }
+            }
+        }
+
+        let attention = attention_output().await;
+        let new_attention_pin = attention.map(|(pin, _)| pin);
+        if new_attention_pin != attention_pin {
+            // Hand the previous attention pin back to its host GPIO config
+            if let Some(old) = attention_pin {
+                apply_config(&mut pins[old], configs[old]);
+            }
+            if let Some(new) = new_attention_pin {
+                info!("GPIO0{} assigned as attention output", 6 + new);
+                pins[new].set_pull(Pull::None);
+                pins[new].set_as_output();
+            }
+            attention_pin = new_attention_pin;
+        }
+        if let Some((pin, level)) = attention {
+            pins[pin].set_level(Level::from(level));
+        }
+
+        let levels = read_levels(&pins);
+        let changed = levels ^ prev_levels;
+        let mut edges = 0u8;
+        for (i, config) in configs.iter().enumerate() {
+            let bit = 1 << i;
+            if changed & bit == 0 || attention_pin == Some(i) {
+                continue;
+            }
+            let rising = levels & bit != 0;
+            if (rising && config.flags & GPIO_EDGE_RISING != 0)
+                || (!rising && config.flags & GPIO_EDGE_FALLING != 0)
+            {
+                edges |= bit;
+            }
+        }
+        prev_levels = levels;
+
+        {
+            let mut state = HEADER_GPIO_STATE.lock().await;
+            state.levels = levels;
+            state.edge_events |= edges;
+        }
+        if edges != 0 {
+            post_host_event_with_payload(HostEvent::GpioEdge, edges as u32).await;
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Controller event queue and the host attention line.
+//!
+//! Tasks post events (state changes, blackouts, button presses, alarms, config
+//! writes, ...) to a bounded FIFO. Each record carries the event type, a payload
+//! and the uptime at which it happened. The host drains the queue one record per
+//! read over I2C (register 0x85). An optional attention output on one of the
+//! header GPIOs is asserted while the queue is non-empty, so the host can wait
+//! for an interrupt instead of polling. The attention pin is driven by the
+//! header GPIO task.
+
+use alloc::collections::VecDeque;
+use defmt::{debug, info, warn};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::config::HOST_EVENT_QUEUE_DEPTH;
+use crate::tasks::config_manager::{get_attention_config, set_attention_config};
+use crate::tasks::header_gpio::NUM_HEADER_GPIOS;
+
+/// Controller event types. The discriminant is the event type in the I2C record
+/// and the bit number in the event mask, and is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum HostEvent {
+    /// External power was lost and the system runs on the supercap.
+    /// Payload: supercap voltage in mV
+    Blackout = 0,
+    /// External power returned during a blackout. Payload: VIN in mV
+    PowerRestored = 1,
+    /// The power button was pressed
+    PowerButton = 2,
+    /// The user button was pressed
+    UserButton = 3,
+    /// The supercap voltage exceeded the alarm threshold. Payload: supercap voltage in mV
+    SupercapAlarm = 4,
+    /// A header GPIO captured an edge. Payload: edge bitfield as in register 0x82
+    GpioEdge = 5,
+    /// The host watchdog expired
+    WatchdogTimeout = 6,
+    /// The state machine changed state. Payload: source state << 8 | target state
+    StateChange = 7,
+    /// A configuration setting was written to flash. Not posted for lifetime statistics
+    /// checkpoints or key migrations. Payload: config key
+    ConfigWrite = 8,
+    /// An "off" slot of the schedule started a graceful shutdown
+    ScheduledShutdown = 9,
+}
+
+impl HostEvent {
+    const fn mask_bit(self) -> u16 {
+        1 << self as u8
+    }
+}
+
+/// A queued event
+#[derive(Clone, Copy, defmt::Format)]
+struct HostEventRecord {
+    event: HostEvent,
+    payload: u32,
+    /// Uptime in milliseconds
+    timestamp: u32,
+}
+
+/// Size of an event record in the I2C wire format
+pub const HOST_EVENT_RECORD_SIZE: usize = 11;
+
+/// Pin value that disables the attention output
+pub const ATTENTION_PIN_DISABLED: u8 = 0xff;
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct AttentionConfig {
+    /// Header GPIO index (0=GPIO06, 1=GPIO07, 2=GPIO08) or ATTENTION_PIN_DISABLED
+    pub pin: u8,
+    pub active_high: bool,
+    /// Events that are queued and assert the attention line, bit N = event type N
+    pub event_mask: u16,
+}
+
+impl AttentionConfig {
+    /// Validate and build an attention config
+    pub fn from_parts(pin: u8, active_high: u8, event_mask: u16) -> Option<Self> {
+        if pin as usize >= NUM_HEADER_GPIOS && pin != ATTENTION_PIN_DISABLED {
+            return None;
+        }
+        if active_high > 1 {
+            return None;
+        }
+        Some(Self {
+            pin,
+            active_high: active_high != 0,
+            event_mask,
+        })
+    }
+
+    /// Packed representation stored in the config: pin in bits 0-7,
+    /// polarity in bits 8-15 and event mask in bits 16-31
+    pub fn to_u32(self) -> u32 {
+        (self.pin as u32) | ((self.active_high as u32) << 8) | ((self.event_mask as u32) << 16)
+    }
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        Self::from_parts(value as u8, (value >> 8) as u8, (value >> 16) as u16)
+    }
+
+    /// Attention config in I2C wire format: pin, polarity, event mask (u16 BE)
+    pub fn to_bytes(self) -> [u8; 4] {
+        let mask = self.event_mask.to_be_bytes();
+        [self.pin, self.active_high as u8, mask[0], mask[1]]
+    }
+
+    fn pin_index(&self) -> Option<usize> {
+        (self.pin != ATTENTION_PIN_DISABLED).then_some(self.pin as usize)
+    }
+}
+
+struct HostEventState {
+    config: AttentionConfig,
+    queue: VecDeque<HostEventRecord>,
+    /// Events dropped because the queue was full, since the last read
+    dropped: u8,
+}
+
+/// Attention output disabled, active low, all events enabled. Matches DEFAULT_ATTENTION_CONFIG.
+const DEFAULT_ATTENTION: AttentionConfig = AttentionConfig {
+    pin: ATTENTION_PIN_DISABLED,
+    active_high: false,
+    event_mask: 0xffff,
+};
+
+static HOST_EVENTS: Mutex<CriticalSectionRawMutex, HostEventState> =
+    Mutex::new(HostEventState {
+        config: DEFAULT_ATTENTION,
+        queue: VecDeque::new(),
+        dropped: 0,
+    });
+
+/// Load the attention config. Must be called after the configuration has been loaded.
+pub async fn init_host_events() {
+    let config =
+        AttentionConfig::from_u32(get_attention_config().await).unwrap_or(DEFAULT_ATTENTION);
+    info!("Host attention config: {}", config);
+    let mut events = HOST_EVENTS.lock().await;
+    events.config = config;
+    events
+        .queue
+        .retain(|record| config.event_mask & record.event.mask_bit() != 0);
+}
+
+/// Queue an event without a payload for the host
+pub async fn post_host_event(event: HostEvent) {
+    post_host_event_with_payload(event, 0).await;
+}
+
+/// Queue an event for the host. Events outside the event mask are dropped. If the
+/// queue is full, the oldest event is discarded.
+pub async fn post_host_event_with_payload(event: HostEvent, payload: u32) {
+    let mut events = HOST_EVENTS.lock().await;
+    if events.config.event_mask & event.mask_bit() == 0 {
+        return;
+    }
+    debug!("Host event: {} ({})", event, payload);
+    if events.queue.len() >= HOST_EVENT_QUEUE_DEPTH {
+        warn!("Host event queue full, dropping oldest event");
+        events.queue.pop_front();
+        events.dropped = events.dropped.saturating_add(1);
+    }
+    events.queue.push_back(HostEventRecord {
+        event,
+        payload,
+        timestamp: Instant::now().as_millis() as u32,
+    });
+}
+
+/// Pop the oldest event in I2C wire format: pending count including this record (u8),
+/// event type (u8), payload (u32 BE), uptime in ms (u32 BE), events dropped since the
+/// last read (u8). An empty queue reads as all zeros. Popping the last record releases
+/// the attention line.
+pub async fn pop_host_event() -> [u8; HOST_EVENT_RECORD_SIZE] {
+    let mut events = HOST_EVENTS.lock().await;
+    let mut bytes = [0u8; HOST_EVENT_RECORD_SIZE];
+    bytes[0] = events.queue.len().min(u8::MAX as usize) as u8;
+    if let Some(record) = events.queue.pop_front() {
+        bytes[1] = record.event as u8;
+        bytes[2..6].copy_from_slice(&record.payload.to_be_bytes());
+        bytes[6..10].copy_from_slice(&record.timestamp.to_be_bytes());
+    }
+    bytes[10] = core::mem::take(&mut events.dropped);
+    bytes
+}
+
+pub async fn get_host_attention_config() -> AttentionConfig {
+    HOST_EVENTS.lock().await.config
+}
+
+/// Update and persist the attention config. Queued events outside the new mask are dropped.
+pub async fn set_host_attention_config(config: AttentionConfig) {
+    set_attention_config(config.to_u32()).await;
+    let mut events = HOST_EVENTS.lock().await;
+    events.config = config;
+    events
+        .queue
+        .retain(|record| config.event_mask & record.event.mask_bit() != 0);
+}
+
+/// Attention pin and the level it should be driven to, if the attention output is enabled
+pub async fn attention_output() -> Option<(usize, bool)> {
+    let events = HOST_EVENTS.lock().await;
+    let pin = events.config.pin_index()?;
+    let asserted = !events.queue.is_empty();
+    Some((pin, asserted == events.config.active_high))
+}
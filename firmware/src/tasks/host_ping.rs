@@ -0,0 +1,95 @@
+//! Explicit host watchdog pings.
+//!
+//! By default any I2C transaction pings the host watchdog. The host can send
+//! explicit pings with a sequence number instead (register 0x1b), and can make
+//! them the only thing that pings the watchdog (register 0x1d), so that a
+//! daemon stuck in a loop that only reads voltages does not look alive. The
+//! controller tracks the sequence numbers and the inter-ping jitter so that the
+//! host can check its own timing (register 0x1c).
+
+use defmt::debug;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+/// Size of the ping status in the I2C wire format
+pub const PING_STATUS_SIZE: usize = 16;
+
+struct PingState {
+    last_seq: Option<u32>,
+    last_ping: Option<Instant>,
+    /// Sequence numbers skipped between consecutive pings
+    missed: u32,
+    last_interval_ms: u32,
+    min_interval_ms: u32,
+    max_interval_ms: u32,
+    /// Smoothed mean deviation between consecutive intervals (as in RFC 3550)
+    jitter_ms: f32,
+}
+
+static PING_STATE: Mutex<CriticalSectionRawMutex, PingState> = Mutex::new(PingState {
+    last_seq: None,
+    last_ping: None,
+    missed: 0,
+    last_interval_ms: 0,
+    min_interval_ms: u32::MAX,
+    max_interval_ms: 0,
+    jitter_ms: 0.0,
+});
+
+/// Record an explicit ping with sequence number `seq`
+pub async fn record_host_ping(seq: u32) {
+    let now = Instant::now();
+    let mut state = PING_STATE.lock().await;
+    if let Some(last_seq) = state.last_seq {
+        // A lower sequence number means the host daemon restarted
+        if seq > last_seq {
+            state.missed = state.missed.saturating_add(seq - last_seq - 1);
+        }
+    }
+    if let Some(last_ping) = state.last_ping {
+        let interval_ms = now.duration_since(last_ping).as_millis() as u32;
+        if state.min_interval_ms != u32::MAX {
+            let deviation = (interval_ms as f32 - state.last_interval_ms as f32).abs();
+            state.jitter_ms += (deviation - state.jitter_ms) / 16.0;
+        }
+        state.last_interval_ms = interval_ms;
+        state.min_interval_ms = state.min_interval_ms.min(interval_ms);
+        state.max_interval_ms = state.max_interval_ms.max(interval_ms);
+    }
+    state.last_seq = Some(seq);
+    state.last_ping = Some(now);
+    debug!("Host ping {}", seq);
+}
+
+/// Clear the ping statistics
+pub async fn reset_host_ping_stats() {
+    let mut state = PING_STATE.lock().await;
+    state.missed = 0;
+    state.last_interval_ms = 0;
+    state.min_interval_ms = u32::MAX;
+    state.max_interval_ms = 0;
+    state.jitter_ms = 0.0;
+}
+
+/// Ping status in I2C wire format: last sequence number (u32 BE), missed sequence
+/// numbers (u32 BE), then the last, minimum and maximum interval between pings and
+/// the jitter in milliseconds (u16 BE each, saturating). Intervals are zero until two
+/// pings have been seen.
+pub async fn get_host_ping_status() -> [u8; PING_STATUS_SIZE] {
+    let state = PING_STATE.lock().await;
+    let saturate = |ms: u32| (ms.min(u16::MAX as u32) as u16).to_be_bytes();
+    let min_interval_ms = if state.min_interval_ms == u32::MAX {
+        0
+    } else {
+        state.min_interval_ms
+    };
+    let mut bytes = [0u8; PING_STATUS_SIZE];
+    bytes[0..4].copy_from_slice(&state.last_seq.unwrap_or(0).to_be_bytes());
+    bytes[4..8].copy_from_slice(&state.missed.to_be_bytes());
+    bytes[8..10].copy_from_slice(&saturate(state.last_interval_ms));
+    bytes[10..12].copy_from_slice(&saturate(min_interval_ms));
+    bytes[12..14].copy_from_slice(&saturate(state.max_interval_ms));
+    bytes[14..16].copy_from_slice(&saturate(state.jitter_ms as u32));
+    bytes
+}
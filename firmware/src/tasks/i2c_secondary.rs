@@ -1,28 +1,67 @@
 use super::flash_writer::FLASH_WRITER_STATUS;
 use crate::config::{
     FLASH_WRITE_BLOCK_SIZE, FW_VERSION, I2C_ADDR, IIN_MAX_VALUE, MAX_TEMPERATURE_VALUE,
-    MIN_TEMPERATURE_VALUE, VIN_MAX_VALUE, VSCAP_MAX_VALUE,
+    MIN_TELEMETRY_LOG_INTERVAL_S, MIN_TEMPERATURE_VALUE, SCHEDULE_SLOTS, VIN_MAX_VALUE,
+    VSCAP_MAX_VALUE,
 };
+use crate::analog_filter::FilterConfig;
 use crate::config_resources::I2CSecondaryResources;
+use crate::rtc::{get_rtc_status, set_rtc_epoch_ms};
+use crate::tasks::calibration::{
+    CalibrationChannel, get_calibration_result, start_calibration, start_two_point_calibration,
+};
 use crate::tasks::config_manager::{
     get_auto_restart, get_iin_correction_scale, get_solo_depleting_timeout_ms,
     get_vin_correction_scale, get_vscap_correction_scale, get_vscap_power_off_threshold,
     get_vscap_power_on_threshold, set_auto_restart, set_iin_correction_scale,
     set_solo_depleting_timeout_ms, set_vin_correction_scale, set_vscap_correction_scale,
     set_vscap_power_off_threshold, set_vscap_power_on_threshold, get_usb_port_state, set_usb_port_state,
-    get_hardware_version, set_hardware_version,
+    get_hardware_version, set_hardware_version, get_vin_correction_offset,
+    get_vscap_correction_offset, get_iin_correction_offset, set_vin_correction_offset,
+    set_vscap_correction_offset, set_iin_correction_offset, get_vin_filter_config,
+    get_vscap_filter_config, get_iin_filter_config, set_vin_filter_config,
+    set_vscap_filter_config, set_iin_filter_config, get_adc3_scale, get_adc3_offset,
+    set_adc3_scale, set_adc3_offset, set_telemetry_log_interval, get_explicit_ping_only,
+    set_explicit_ping_only, get_boot_watchdog_timeout_s, set_boot_watchdog_timeout_s,
+    get_watchdog_timeout_ms, set_watchdog_timeout_ms, set_liveness_timeout_min,
+    set_liveness_recovery,
 };
 use crate::tasks::flash_writer::{
     FLASH_WRITE_REQUEST_CHANNEL, FlashUpdateState, FlashWriteCommand,
 };
 use crate::tasks::gpio_input::INPUTS;
+use crate::tasks::header_gpio::{
+    GpioPinConfig, NUM_HEADER_GPIOS, configure_gpio, get_gpio_configs, get_gpio_levels,
+    set_gpio_levels, take_gpio_edge_events,
+};
+use crate::tasks::host_events::{
+    AttentionConfig, get_host_attention_config, pop_host_event, set_host_attention_config,
+};
+use crate::crash_log::get_reset_report;
+use crate::tasks::event_log::{erase_event_log, read_event_log_chunk};
+use crate::tasks::lifetime_stats::get_lifetime_stats_block;
+use crate::tasks::telemetry_log::{
+    erase_telemetry_log, get_telemetry_log_status, read_telemetry_log_chunk,
+};
+use crate::tasks::liveness::{LivenessRecovery, get_liveness_status};
+use crate::tasks::boot_code::{disarm_boot_code_decoding, get_boot_code_status};
+use crate::tasks::host_ping::{get_host_ping_status, record_host_ping, reset_host_ping_stats};
+use crate::tasks::schedule::{
+    ScheduleSlot, get_schedule_bytes, is_stay_awake, set_schedule_slot, set_stay_awake,
+};
+use crate::tasks::telemetry_stats::{get_telemetry_stats, reset_telemetry_stats};
+use crate::tasks::waveform::{get_waveform_status, read_waveform_chunk, rearm_waveform_capture};
+use crate::tasks::shutdown_reason::get_shutdown_report;
+use crate::tasks::transition_history::get_transition_history_page;
 use crate::tasks::led_blinker::{
     LED_BLINKER_EVENT_CHANNEL, LEDBlinkerEvents, LedOverrideCommand, NUM_LEDS as LED_NUM_LEDS,
     get_led_brightness, set_led_brightness,
 };
 use crate::tasks::state_machine::{
-    STATE_MACHINE_EVENT_CHANNEL, StateMachineEvents, get_state_machine_state, state_as_u8,
+    STATE_MACHINE_EVENT_CHANNEL, StateMachineEvents, get_host_watchdog_elapsed_ms,
+    get_host_watchdog_timeout_ms, get_state_machine_state, get_state_timing, state_as_u8,
 };
+use crate::tasks::test_mode::{get_test_report, request_test_run};
 use crc::{CRC_32_ISO_HDLC, Crc};
 use defmt::{debug, error, info};
 use embassy_executor::task;
@@ -38,14 +77,16 @@ use embassy_rp::{bind_interrupts, i2c, i2c_slave};
 // - Read  0x10: Query Raspi power state (1 byte, 0=off, 1=on)
 // - Write 0x10 0x00: Set Raspi power off
 // - Read  0x12: Query watchdog timeout (2 bytes, ms)
-// - Write 0x12 [NN NN]: Set watchdog timeout to NNNN ms (u16, big-endian)
+// - Write 0x12 [NN NN]: Set watchdog timeout to NNNN ms (u16, big-endian); non-zero values
+//     are also stored as the timeout used after the first ping with the boot watchdog
 // - Write 0x12 0x00 0x00: Disable watchdog
 // - Read  0x13: Query power-on supercap threshold voltage (2 bytes, scaled to 00..VSCAP_MAX_VALUE)
 // - Write 0x13 [NN NN]: Set power-on supercap threshold voltage to NNNN/0xFFFF*VSCAP_MAX_VALUE V (u16, big-endian)
 // - Read  0x14: Query power-off supercap threshold voltage (2 bytes, scaled to 00..VSCAP_MAX_VALUE))
 // - Write 0x14 [NN NN]: Set power-off supercap threshold voltage to NNNN/0xFFFF*VSCAP_MAX_VALUE V (u16, big-endian)
 // - Read  0x15: Query state machine state (1 byte, placeholder)
-// - Read  0x16: Query watchdog elapsed (always returns 0x00)
+// - Read  0x16: Query time since the last host watchdog ping (2 bytes, ms, big-endian,
+//     saturating; 0 when the watchdog is disabled)
 // - Read  0x17: Query LED brightness setting (1 byte)
 // - Write 0x17 [NN]: Set LED brightness to NN
 // - Read  0x18: Query auto restart setting (1 byte, 0=disabled, 1=enabled)
@@ -54,12 +95,30 @@ use embassy_rp::{bind_interrupts, i2c, i2c_slave};
 // - Write 0x19 [NN NN NN NN]: Set solo depleting timeout to NNNNNNNN ms (u32, big-endian)
 // - Read  0x1a: Query USB port enable state (1 byte, bitfield: bit 0=USB0, bit 1=USB1, bit 2=USB2, bit 3=USB3)
 // - Write 0x1a [NN]: Set USB port enable state (bitfield: 0=disabled, 1=enabled)
+// - Write 0x1b [SS SS SS SS]: Ping the host watchdog with sequence number SSSSSSSS (u32 BE)
+// - Read  0x1c: Query host ping status (16 bytes: last sequence number (u32 BE), missed
+//     sequence numbers (u32 BE), last, min and max ping interval and jitter (ms, u16 BE each))
+// - Write 0x1c [ANY]: Reset host ping statistics
+// - Read  0x1d: Query explicit ping mode (1 byte, 0=any I2C transaction pings the watchdog,
+//     1=only 0x1b pings it)
+// - Write 0x1d [NN]: Set explicit ping mode
+// - Read  0x1e: Query boot watchdog deadline (2 bytes, seconds, big-endian, 0=disabled)
+// - Write 0x1e [NN NN]: Set boot watchdog deadline to NNNN s (u16, big-endian, 0=disabled)
+// - Read  0x1f: Query CM5 liveness status (8 bytes: timeout (minutes, u16 BE, 0=disabled),
+//     recovery action, seconds since the last LED_ACTIVE change (u16 BE), LED_PWR blinking
+//     flag, hangs detected since boot (u16 BE))
+// - Write 0x1f [TT TT AA]: Set liveness timeout to TTTT minutes (u16 BE, 0=disabled) and
+//     recovery action AA (0=log only, 1=power button click, 2=power cycle)
 // - Read  0x20: Query DC IN voltage (2 bytes, scaled u16)
 // - Read  0x21: Query supercap voltage (2 bytes, scaled u16)
 // - Read  0x22: Query DC IN current (2 bytes, scaled u16)
 // - Read  0x23: Query MCU temperature (2 bytes, scaled u16)
 // - Read  0x24: Query PCB temperature (2 bytes, scaled u16)
 // - Read  0x25: Query device unique ID (8 bytes)
+// - Read  0x26: Query ADC3 user analog input (4 bytes, f32 little-endian, scaled to the configured unit)
+// - Read  0x27: Query raw ADC counts (8 bytes: VIN, VSCAP, IIN, ADC3, u16 big-endian each)
+// - Read  0x28: Query CM5 boot error code (3 bytes: decoder status (0=idle, 1=decoding,
+//     2=code decoded), long flashes, short flashes)
 // - Write 0x60 [NUM_LEDS * 6 bytes]: LED override (R,G,B,Alpha,TransitionMs_BE per LED).
 //     Only processed in OperationalCoOp state. Alpha=0 means no override for that LED.
 //     Overrides auto-clear after 5 seconds without updates.
@@ -71,6 +130,85 @@ use embassy_rp::{bind_interrupts, i2c, i2c_slave};
 // - Write 0x51 [NN NN NN NN]: Set VSCAP correction scale to NNNNNNNN (f32, big-endian)
 // - Read  0x52: Query IIN correction scale (4 bytes, f32)
 // - Write 0x52 [NN NN NN NN]: Set IIN correction scale to NNNNNNNN (f32, big-endian)
+// - Read  0x53: Query VIN correction offset (4 bytes, f32, V)
+// - Write 0x53 [NN NN NN NN]: Set VIN correction offset to NNNNNNNN V (f32, little-endian)
+// - Read  0x54: Query VSCAP correction offset (4 bytes, f32, V)
+// - Write 0x54 [NN NN NN NN]: Set VSCAP correction offset to NNNNNNNN V (f32, little-endian)
+// - Read  0x55: Query IIN correction offset (4 bytes, f32, A)
+// - Write 0x55 [NN NN NN NN]: Set IIN correction offset to NNNNNNNN A (f32, little-endian)
+// - Read  0x56: Query ADC3 scale (4 bytes, f32)
+// - Write 0x56 [NN NN NN NN]: Set ADC3 scale, applied to the pin voltage (f32, little-endian)
+// - Read  0x57: Query ADC3 offset (4 bytes, f32)
+// - Write 0x57 [NN NN NN NN]: Set ADC3 offset (f32, little-endian)
+// - Read  0x58: Query calibration result (14 bytes: status, channel, residual error (i32 BE, mV/mA),
+//     correction scale (f32 LE), correction offset (f32 LE))
+// - Write 0x58 [CH] [NN NN NN NN]: Calibrate channel CH (0=VIN, 1=VSCAP, 2=IIN) gain against the
+//     true value NNNNNNNN (u32 BE, mV for voltages, mA for current)
+// - Write 0x59 [CH] [PT] [NN NN NN NN]: Capture two-point calibration point PT (0=first, 1=second)
+//     of channel CH at true value NNNNNNNN (u32 BE, mV/mA). The second point computes gain and offset.
+// - Read  0x5b: Query analog filter config (6 bytes: filter type and window for VIN, VSCAP, IIN)
+// - Write 0x5b [CH] [TT] [NN]: Set filter of channel CH (0=VIN, 1=VSCAP, 2=IIN) to type TT
+//     (0=moving average, 1=EMA, 2=median, 3=oversample) with window NN (1..=32 samples)
+// - Read  0x70: Query factory test report (1 + 13 bytes: status, then one result per check)
+// - Write 0x71 [ANY]: Re-run the factory test sequence (test mode only)
+// - Read  0x80: Query header GPIO config (6 bytes: mode and flags for GPIO06, GPIO07, GPIO08)
+// - Write 0x80 [PP] [MM] [FF]: Configure header GPIO PP (0=GPIO06, 1=GPIO07, 2=GPIO08) to mode MM
+//     (0=input, 1=input pull-up, 2=input pull-down, 3=output, 4=open-drain) with flags FF
+//     (bit 0=capture rising edges, bit 1=capture falling edges, bit 7=initial output level high)
+// - Read  0x81: Query header GPIO levels (1 byte, bit 0=GPIO06, bit 1=GPIO07, bit 2=GPIO08)
+// - Write 0x81 [MM] [LL]: Set the output levels LL of the header GPIOs selected by mask MM
+// - Read  0x82: Query and clear latched header GPIO edge events (1 byte, bitfield as in 0x81)
+// - Read  0x84: Query host attention config (4 bytes: pin, active high, event mask u16 BE)
+// - Write 0x84 [PP] [AA] [MM MM]: Set attention output to header GPIO PP (0..2, 0xff=disabled),
+//     active high if AA=1, for the event types in mask MMMM (u16 BE, bit N = type N)
+// - Read  0x85: Pop the oldest host event (11 bytes: pending count, type, payload u32 BE,
+//     uptime ms u32 BE, dropped count). A pending count of 0 means the queue was empty.
+// - WriteRead 0x86 [PP]: Query page PP of the state transition history (45 bytes: total
+//     transition count, then 4 records of 11 bytes, newest first. Record: uptime ms u32 BE,
+//     from state, to state, event, VIN mV u16 BE, VSCAP mV u16 BE). PP defaults to 0.
+// - WriteRead 0x88 [NN NN]: Query up to 8 flash event log records starting at record NNNN
+//     (u16 BE, 0=oldest) (107 bytes: record count u16 BE, records in the chunk, then 13-byte
+//     records: kind, payload u32 BE, uptime ms u32 BE, UTC seconds u32 BE (0 if the clock was
+//     not set)). Unused record slots are zero.
+// - Write 0x89 [ANY]: Erase the flash event log
+// - Read  0x8a: Query reset report (75 bytes: reset reason, crash kind (0=none, 1=panic,
+//     2=HardFault), PC or panic line u32 BE, LR u32 BE, message length, 64-byte panic message)
+// - Read  0x8b: Query shutdown reasons (6 bytes: reason, state, graceful flag of the last
+//     shutdown before the latest reset, then the same for the current run)
+// - Read  0x8c: Query state timing (21 bytes: state, time in state, then remaining time before
+//     the host watchdog, solo depleting, shutdown wait and off-state timeouts; u32 BE ms each,
+//     0xFFFFFFFF if not pending)
+// - Read  0x90: Query lifetime statistics (32 bytes: version, 3 reserved, powered-on seconds,
+//     starts, blackouts ridden through, blackouts ending in shutdown, watchdog recoveries
+//     (u32 BE each), input energy in joules (u64 BE))
+// - Read  0x91: Query telemetry statistics (90 bytes: for the 1 minute, 1 hour and since boot
+//     windows, min, max and mean of VIN, VSCAP, IIN, MCU temp and PCB temp, scaled u16 BE as
+//     in 0x20-0x24)
+// - Write 0x92 [ANY]: Reset telemetry statistics
+// - Read  0x93: Query telemetry log status (10 bytes: interval in seconds (u16 BE), oldest
+//     and next record sequence number (u32 BE each))
+// - Write 0x93 [SS SS]: Set telemetry log interval in seconds (u16 BE, at least 10)
+// - WriteRead 0x94 [NN NN NN NN]: Read telemetry log records from sequence number NNNNNNNN
+//     (u32 BE) (129 bytes: record count (up to 8), then 16-byte records: sequence number
+//     (u32 BE, top bit set if the timestamp is UTC), UTC or uptime seconds (u32 BE), VIN,
+//     VSCAP, IIN, MCU temp (u16 BE, scaled as 0x20-0x23))
+// - Write 0x95 [ANY]: Erase the telemetry log
+// - Read  0x96: Query power-loss waveform capture status (11 bytes: state (0=armed,
+//     1=capturing, 2=frozen), sample interval in us (u16 BE), trigger sample index (u16 BE),
+//     sample count (u16 BE), trigger uptime in ms (u32 BE))
+// - Write 0x96 [ANY]: Discard the captured waveform and re-arm the capture
+// - WriteRead 0x97 [NN NN]: Read waveform samples from index NNNN (u16 BE, 0=oldest) (96 bytes:
+//     16 samples of VIN, VSCAP, IIN (u16 BE each, scaled as 0x20-0x22))
+// - Read  0x98: Query the software clock (11 bytes: source (0=not set, 1=set by host,
+//     2=kept across a reset), UTC milliseconds since the Unix epoch (u64 BE), drift ppm (i16 BE))
+// - Write 0x98 [TT TT TT TT TT TT TT TT]: Set the software clock to UTC milliseconds since the
+//     Unix epoch (u64 BE)
+// - Read  0x99: Query the power schedule (32 bytes: 8 slots of weekday mask (bit 0=Monday),
+//     action (0=off, 1=on), minute of the day in UTC (u16 BE))
+// - Write 0x99 [II] [DD] [AA] [MM MM]: Set schedule slot II (0..7) to weekday mask DD (0=disabled),
+//     action AA and UTC minute of the day MMMM
+// - Read  0x9a: Query the stay awake inhibit (1 byte)
+// - Write 0x9a [BB]: Set (BB != 0) or clear the stay awake inhibit for scheduled shutdowns
 
 //
 // Device Firmware Update (DFU) protocol:
@@ -149,7 +287,6 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
     let mut device = i2c_slave::I2cSlave::new(r.i2c, r.scl, r.sda, Irqs, config);
     let mut dfu_crc_error: bool = false;
     let mut data_length_error: bool = false;
-    let mut host_watchdog_timeout_ms = 0u16; // Keep a local copy of the watchdog timeout
 
     let state = 0;
 
@@ -157,7 +294,13 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
 
     loop {
         let mut buf = [0u8; FLASH_WRITE_BLOCK_SIZE + 10];
-        match device.listen(&mut buf).await {
+        let mut explicit_ping = false;
+        let command = device.listen(&mut buf).await;
+        // Any transaction shows that the CM5 has booted
+        if command.is_ok() {
+            disarm_boot_code_decoding().await;
+        }
+        match command {
             Ok(i2c_slave::Command::GeneralCall(len)) => {
                 error!("General call write received: {}", buf[..len]);
             }
@@ -224,7 +367,10 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                             STATE_MACHINE_EVENT_CHANNEL
                                 .send(StateMachineEvents::SetHostWatchdogTimeout(timeout))
                                 .await;
-                            host_watchdog_timeout_ms = timeout; // Update local copy
+                            // Keep the timeout for the boot watchdog
+                            if timeout > 0 && timeout != get_watchdog_timeout_ms().await {
+                                set_watchdog_timeout_ms(timeout).await;
+                            }
                         }
                     }
                     // Set supercap power-on threshold voltage
@@ -283,6 +429,55 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         info!("Setting USB port state to: 0x{:02x}", port_bits);
                         set_usb_port_state(port_bits).await;
                     }
+                    // Explicit host watchdog ping
+                    0x1b => {
+                        if len != 5 {
+                            error!("Invalid ping command length");
+                            continue;
+                        }
+                        record_host_ping(u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]])).await;
+                        explicit_ping = true;
+                    }
+                    // Reset host ping statistics
+                    0x1c => {
+                        info!("Resetting host ping statistics");
+                        reset_host_ping_stats().await;
+                    }
+                    // Set explicit ping mode
+                    0x1d => {
+                        if len != 2 {
+                            error!("Invalid ping mode command length");
+                            continue;
+                        }
+                        let explicit_only = buf[1] != 0;
+                        info!("Setting explicit ping only to {}", explicit_only);
+                        set_explicit_ping_only(explicit_only).await;
+                    }
+                    // Set boot watchdog deadline
+                    0x1e => {
+                        if len != 3 {
+                            error!("Invalid boot watchdog command length");
+                            continue;
+                        }
+                        let timeout_s = u16::from_be_bytes([buf[1], buf[2]]);
+                        info!("Setting boot watchdog deadline to {} s", timeout_s);
+                        set_boot_watchdog_timeout_s(timeout_s).await;
+                    }
+                    // Set CM5 liveness detection
+                    0x1f => {
+                        if len != 4 {
+                            error!("Invalid liveness command length");
+                            continue;
+                        }
+                        let timeout_min = u16::from_be_bytes([buf[1], buf[2]]);
+                        let Some(recovery) = LivenessRecovery::from_u8(buf[3]) else {
+                            error!("Invalid liveness recovery: {}", buf[3]);
+                            continue;
+                        };
+                        info!("Setting liveness timeout to {} min, recovery {}", timeout_min, recovery);
+                        set_liveness_timeout_min(timeout_min).await;
+                        set_liveness_recovery(recovery as u8).await;
+                    }
                     // LED override
                     0x60 => {
                         let expected_len = 1 + LED_NUM_LEDS * 6;
@@ -454,10 +649,232 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         info!("Setting IIN correction scale to {}", value);
                         set_iin_correction_scale(value).await;
                     }
+                    // Set VIN correction offset
+                    0x53 => {
+                        if len != 6 {
+                            error!("Invalid VIN correction offset command length");
+                            continue;
+                        }
+                        let value = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+                        info!("Setting VIN correction offset to {}", value);
+                        set_vin_correction_offset(value).await;
+                    }
+                    // Set VSCAP correction offset
+                    0x54 => {
+                        if len != 6 {
+                            error!("Invalid VSCAP correction offset command length");
+                            continue;
+                        }
+                        let value = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+                        info!("Setting VSCAP correction offset to {}", value);
+                        set_vscap_correction_offset(value).await;
+                    }
+                    // Set IIN correction offset
+                    0x55 => {
+                        if len != 6 {
+                            error!("Invalid IIN correction offset command length");
+                            continue;
+                        }
+                        let value = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+                        info!("Setting IIN correction offset to {}", value);
+                        set_iin_correction_offset(value).await;
+                    }
+                    // Set ADC3 scale
+                    0x56 => {
+                        if len != 6 {
+                            error!("Invalid ADC3 scale command length");
+                            continue;
+                        }
+                        let value = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+                        info!("Setting ADC3 scale to {}", value);
+                        set_adc3_scale(value).await;
+                    }
+                    // Set ADC3 offset
+                    0x57 => {
+                        if len != 6 {
+                            error!("Invalid ADC3 offset command length");
+                            continue;
+                        }
+                        let value = f32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+                        info!("Setting ADC3 offset to {}", value);
+                        set_adc3_offset(value).await;
+                    }
+                    // Start guided calibration
+                    0x58 => {
+                        if len != 6 {
+                            error!("Invalid calibration command length");
+                            continue;
+                        }
+                        let Some(channel) = CalibrationChannel::from_u8(buf[1]) else {
+                            error!("Invalid calibration channel: {}", buf[1]);
+                            continue;
+                        };
+                        let true_value = u32::from_be_bytes([buf[2], buf[3], buf[4], buf[5]]);
+                        info!("Calibrating {:?} to {}", channel, true_value);
+                        start_calibration(channel, true_value).await;
+                    }
+                    // Capture a two-point calibration point
+                    0x59 => {
+                        if len != 7 {
+                            error!("Invalid two-point calibration command length");
+                            continue;
+                        }
+                        let Some(channel) = CalibrationChannel::from_u8(buf[1]) else {
+                            error!("Invalid calibration channel: {}", buf[1]);
+                            continue;
+                        };
+                        if buf[2] > 1 {
+                            error!("Invalid calibration point: {}", buf[2]);
+                            continue;
+                        }
+                        let true_value = u32::from_be_bytes([buf[3], buf[4], buf[5], buf[6]]);
+                        info!(
+                            "Capturing calibration point {} of {:?} at {}",
+                            buf[2], channel, true_value
+                        );
+                        start_two_point_calibration(channel, buf[2] == 1, true_value).await;
+                    }
+                    // Set analog filter config
+                    0x5b => {
+                        if len != 4 {
+                            error!("Invalid filter config command length");
+                            continue;
+                        }
+                        let Some(channel) = CalibrationChannel::from_u8(buf[1]) else {
+                            error!("Invalid filter channel: {}", buf[1]);
+                            continue;
+                        };
+                        let Some(config) = FilterConfig::from_parts(buf[2], buf[3]) else {
+                            error!("Invalid filter config: {} {}", buf[2], buf[3]);
+                            continue;
+                        };
+                        info!("Setting {:?} filter to {}", channel, config);
+                        match channel {
+                            CalibrationChannel::Vin => set_vin_filter_config(config).await,
+                            CalibrationChannel::Vscap => set_vscap_filter_config(config).await,
+                            CalibrationChannel::Iin => set_iin_filter_config(config).await,
+                        }
+                    }
+                    // Re-run the factory test sequence
+                    0x71 => {
+                        info!("Requesting factory test run");
+                        request_test_run();
+                    }
+                    // Configure a header GPIO
+                    0x80 => {
+                        if len != 4 {
+                            error!("Invalid GPIO config command length");
+                            continue;
+                        }
+                        let pin = buf[1] as usize;
+                        if pin >= NUM_HEADER_GPIOS {
+                            error!("Invalid GPIO pin: {}", pin);
+                            continue;
+                        }
+                        let Some(config) = GpioPinConfig::from_parts(buf[2], buf[3]) else {
+                            error!("Invalid GPIO config: {} {}", buf[2], buf[3]);
+                            continue;
+                        };
+                        info!("Configuring GPIO {} as {}", pin, config);
+                        configure_gpio(pin, config).await;
+                    }
+                    // Set header GPIO output levels
+                    0x81 => {
+                        if len != 3 {
+                            error!("Invalid GPIO level command length");
+                            continue;
+                        }
+                        debug!("Setting GPIO levels {:02x} (mask {:02x})", buf[2], buf[1]);
+                        set_gpio_levels(buf[1], buf[2]).await;
+                    }
+                    // Set host attention config
+                    0x84 => {
+                        if len != 5 {
+                            error!("Invalid attention config command length");
+                            continue;
+                        }
+                        let mask = u16::from_be_bytes([buf[3], buf[4]]);
+                        let Some(config) = AttentionConfig::from_parts(buf[1], buf[2], mask) else {
+                            error!("Invalid attention config: {} {} {:04x}", buf[1], buf[2], mask);
+                            continue;
+                        };
+                        info!("Setting attention config to {}", config);
+                        set_host_attention_config(config).await;
+                    }
+                    // Erase the flash event log
+                    0x89 => {
+                        info!("Erasing event log");
+                        erase_event_log().await;
+                    }
+                    // Reset telemetry statistics
+                    0x92 => {
+                        info!("Resetting telemetry statistics");
+                        reset_telemetry_stats().await;
+                    }
+                    // Set telemetry log interval
+                    0x93 => {
+                        if len != 3 {
+                            error!("Invalid telemetry log interval command length");
+                            continue;
+                        }
+                        let interval = u16::from_be_bytes([buf[1], buf[2]]);
+                        if interval < MIN_TELEMETRY_LOG_INTERVAL_S {
+                            error!("Invalid telemetry log interval: {}", interval);
+                            continue;
+                        }
+                        info!("Setting telemetry log interval to {} s", interval);
+                        set_telemetry_log_interval(interval).await;
+                    }
+                    // Erase the telemetry log
+                    0x95 => {
+                        info!("Erasing telemetry log");
+                        erase_telemetry_log();
+                    }
+                    // Re-arm the waveform capture
+                    0x96 => {
+                        info!("Re-arming waveform capture");
+                        rearm_waveform_capture().await;
+                    }
+                    // Set the software clock
+                    0x98 => {
+                        if len != 9 {
+                            error!("Invalid clock command length");
+                            continue;
+                        }
+                        let mut epoch_ms = [0u8; 8];
+                        epoch_ms.copy_from_slice(&buf[1..9]);
+                        set_rtc_epoch_ms(u64::from_be_bytes(epoch_ms));
+                    }
+                    // Set a schedule slot
+                    0x99 => {
+                        if len != 6 {
+                            error!("Invalid schedule command length");
+                            continue;
+                        }
+                        let index = buf[1] as usize;
+                        if index >= SCHEDULE_SLOTS {
+                            error!("Invalid schedule slot: {}", index);
+                            continue;
+                        }
+                        let Some(slot) = ScheduleSlot::from_bytes([buf[2], buf[3], buf[4], buf[5]]) else {
+                            error!("Invalid schedule slot: {} {} {} {}", buf[2], buf[3], buf[4], buf[5]);
+                            continue;
+                        };
+                        info!("Setting schedule slot {} to {}", index, slot);
+                        set_schedule_slot(index, slot).await;
+                    }
+                    // Set the stay awake inhibit
+                    0x9a => {
+                        if len != 2 {
+                            error!("Invalid stay awake command length");
+                            continue;
+                        }
+                        set_stay_awake(buf[1] != 0).await;
+                    }
                     x => error!("Invalid Write command: {:02x}", x),
                 }
             }
-            Ok(i2c_slave::Command::WriteRead(_)) => {
+            Ok(i2c_slave::Command::WriteRead(len)) => {
                 let inputs = INPUTS.lock().await;
                 match buf[0] {
                     // Query legacy hardware version
@@ -476,7 +893,7 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                     0x10 => respond(&mut device, &[inputs.pg_5v as u8]).await,
                     // Query watchdog timeout
                     0x12 => {
-                        let timeout = host_watchdog_timeout_ms;
+                        let timeout = get_host_watchdog_timeout_ms().await;
                         let timeout_bytes = timeout.to_be_bytes();
                         respond(&mut device, &timeout_bytes).await
                     }
@@ -502,8 +919,11 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         let state_value = state_as_u8(&state);
                         respond(&mut device, &[state_value]).await
                     }
-                    // Query watchdog elapsed time (always returns 0x00)
-                    0x16 => respond(&mut device, &[0]).await,
+                    // Query time since the last host watchdog ping in ms (saturating)
+                    0x16 => {
+                        let elapsed = get_host_watchdog_elapsed_ms().await.min(u16::MAX as u32);
+                        respond(&mut device, &(elapsed as u16).to_be_bytes()).await
+                    }
                     // Query LED brightness setting
                     0x17 => {
                         let brightness = get_led_brightness().await;
@@ -525,6 +945,26 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         let port_bits = get_usb_port_state().await;
                         respond(&mut device, &[port_bits]).await
                     }
+                    // Query host ping status
+                    0x1c => {
+                        let status = get_host_ping_status().await;
+                        respond(&mut device, &status).await
+                    }
+                    // Query explicit ping mode
+                    0x1d => {
+                        let explicit_only = get_explicit_ping_only().await;
+                        respond(&mut device, &[explicit_only as u8]).await
+                    }
+                    // Query boot watchdog deadline
+                    0x1e => {
+                        let timeout_s = get_boot_watchdog_timeout_s().await;
+                        respond(&mut device, &timeout_s.to_be_bytes()).await
+                    }
+                    // Query CM5 liveness status
+                    0x1f => {
+                        let status = get_liveness_status().await;
+                        respond(&mut device, &status).await
+                    }
                     // Query DC IN voltage
                     0x20 => {
                         let voltage = inputs.vin;
@@ -571,6 +1011,24 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         let _ = flash.blocking_unique_id(&mut unique_id);
                         respond(&mut device, &unique_id).await
                     }
+                    // Query ADC3 user analog input
+                    0x26 => {
+                        respond(&mut device, &inputs.adc3.to_le_bytes()).await
+                    }
+                    // Query raw ADC counts
+                    0x27 => {
+                        let mut bytes = [0u8; 8];
+                        bytes[0..2].copy_from_slice(&inputs.vin_raw.to_be_bytes());
+                        bytes[2..4].copy_from_slice(&inputs.vscap_raw.to_be_bytes());
+                        bytes[4..6].copy_from_slice(&inputs.iin_raw.to_be_bytes());
+                        bytes[6..8].copy_from_slice(&inputs.adc3_raw.to_be_bytes());
+                        respond(&mut device, &bytes).await
+                    }
+                    // Query CM5 boot error code
+                    0x28 => {
+                        let status = get_boot_code_status().await;
+                        respond(&mut device, &status).await
+                    }
                     // Read DFU status
                     0x41 => {
                         let dfu_state = get_dfu_state(dfu_crc_error, data_length_error).await;
@@ -602,14 +1060,179 @@ pub async fn i2c_secondary_task(r: I2CSecondaryResources) {
                         let bytes = value.to_le_bytes();
                         respond(&mut device, &bytes).await
                     }
+                    // VIN Correction Offset
+                    0x53 => {
+                        let value = get_vin_correction_offset().await;
+                        respond(&mut device, &value.to_le_bytes()).await
+                    }
+                    // VSCAP Correction Offset
+                    0x54 => {
+                        let value = get_vscap_correction_offset().await;
+                        respond(&mut device, &value.to_le_bytes()).await
+                    }
+                    // IIN Correction Offset
+                    0x55 => {
+                        let value = get_iin_correction_offset().await;
+                        respond(&mut device, &value.to_le_bytes()).await
+                    }
+                    // ADC3 Scale
+                    0x56 => {
+                        let value = get_adc3_scale().await;
+                        respond(&mut device, &value.to_le_bytes()).await
+                    }
+                    // ADC3 Offset
+                    0x57 => {
+                        let value = get_adc3_offset().await;
+                        respond(&mut device, &value.to_le_bytes()).await
+                    }
+                    // Query calibration result
+                    0x58 => {
+                        let result = get_calibration_result().await;
+                        respond(&mut device, &result).await
+                    }
+                    // Query analog filter config
+                    0x5b => {
+                        let mut bytes = [0u8; 6];
+                        for (i, config) in [
+                            get_vin_filter_config().await,
+                            get_vscap_filter_config().await,
+                            get_iin_filter_config().await,
+                        ]
+                        .iter()
+                        .enumerate()
+                        {
+                            bytes[2 * i] = config.filter_type as u8;
+                            bytes[2 * i + 1] = config.window;
+                        }
+                        respond(&mut device, &bytes).await
+                    }
+                    // Query factory test report
+                    0x70 => {
+                        let report = get_test_report().await;
+                        respond(&mut device, &report).await
+                    }
+                    // Query header GPIO config
+                    0x80 => {
+                        let configs = get_gpio_configs().await;
+                        respond(&mut device, &configs).await
+                    }
+                    // Query header GPIO levels
+                    0x81 => {
+                        let levels = get_gpio_levels().await;
+                        respond(&mut device, &[levels]).await
+                    }
+                    // Query and clear header GPIO edge events
+                    0x82 => {
+                        let events = take_gpio_edge_events().await;
+                        respond(&mut device, &[events]).await
+                    }
+                    // Query host attention config
+                    0x84 => {
+                        let config = get_host_attention_config().await;
+                        respond(&mut device, &config.to_bytes()).await
+                    }
+                    // Pop the oldest host event
+                    0x85 => {
+                        let record = pop_host_event().await;
+                        respond(&mut device, &record).await
+                    }
+                    // Query a page of the state transition history
+                    0x86 => {
+                        let page = if len >= 2 { buf[1] } else { 0 };
+                        let history = get_transition_history_page(page).await;
+                        respond(&mut device, &history).await
+                    }
+                    // Query a flash event log record
+                    0x88 => {
+                        let index = if len >= 3 {
+                            u16::from_be_bytes([buf[1], buf[2]])
+                        } else {
+                            0
+                        };
+                        // The flash may be busy for a while; do not stall the input tasks
+                        drop(inputs);
+                        let chunk = read_event_log_chunk(index).await;
+                        respond(&mut device, &chunk).await
+                    }
+                    // Query the reset reason and crash report of the previous run
+                    0x8a => {
+                        let report = get_reset_report().await;
+                        respond(&mut device, &report.to_bytes()).await
+                    }
+                    // Query the shutdown reasons
+                    0x8b => {
+                        let report = get_shutdown_report().await;
+                        respond(&mut device, &report).await
+                    }
+                    // Query time in state and remaining timeouts
+                    0x8c => {
+                        let timing = get_state_timing().await;
+                        respond(&mut device, &timing).await
+                    }
+                    // Query lifetime statistics
+                    0x90 => {
+                        let stats = get_lifetime_stats_block().await;
+                        respond(&mut device, &stats).await
+                    }
+                    // Query telemetry statistics
+                    0x91 => {
+                        let stats = get_telemetry_stats().await;
+                        respond(&mut device, &stats).await
+                    }
+                    // Query telemetry log status
+                    0x93 => {
+                        let status = get_telemetry_log_status().await;
+                        respond(&mut device, &status).await
+                    }
+                    // Read a chunk of the telemetry log
+                    0x94 => {
+                        let start = if len >= 5 {
+                            u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]])
+                        } else {
+                            0
+                        };
+                        // The flash may be busy for a while; do not stall the input tasks
+                        drop(inputs);
+                        let chunk = read_telemetry_log_chunk(start).await;
+                        respond(&mut device, &chunk).await
+                    }
+                    // Query the waveform capture status
+                    0x96 => {
+                        let status = get_waveform_status().await;
+                        respond(&mut device, &status).await
+                    }
+                    // Read a chunk of the captured waveform
+                    0x97 => {
+                        let start = if len >= 3 {
+                            u16::from_be_bytes([buf[1], buf[2]])
+                        } else {
+                            0
+                        };
+                        let chunk = read_waveform_chunk(start).await;
+                        respond(&mut device, &chunk).await
+                    }
+                    // Query the software clock
+                    0x98 => respond(&mut device, &get_rtc_status()).await,
+                    // Query the power schedule
+                    0x99 => {
+                        let schedule = get_schedule_bytes().await;
+                        respond(&mut device, &schedule).await
+                    }
+                    // Query the stay awake inhibit
+                    0x9a => {
+                        let stay_awake = is_stay_awake().await;
+                        respond(&mut device, &[stay_awake as u8]).await
+                    }
                     x => error!("Invalid Write Read command: 0x{:02x}", x),
                 }
             }
             Err(e) => error!("{}", e),
         }
-        // Update watchdog on any I2C activity
-        STATE_MACHINE_EVENT_CHANNEL
-            .send(StateMachineEvents::HostWatchdogPing)
-            .await;
+        // Update watchdog on any I2C activity, or only on explicit pings if so configured
+        if explicit_ping || !get_explicit_ping_only().await {
+            STATE_MACHINE_EVENT_CHANNEL
+                .send(StateMachineEvents::HostWatchdogPing)
+                .await;
+        }
     }
 }
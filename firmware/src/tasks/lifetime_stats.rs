@@ -0,0 +1,197 @@
+//! Lifetime operating statistics.
+//!
+//! Counts powered-on time, system starts, blackouts, host watchdog recoveries and
+//! the energy drawn from the input (the integral of VIN * IIN). The statistics
+//! are kept in RAM and checkpointed to the config flash periodically and after
+//! each counted event, so that flash wear stays low. The host reads them as a
+//! versioned 32-byte block over I2C (register 0x90).
+
+use defmt::{debug, info};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Ticker};
+
+use crate::config::{
+    LIFETIME_STATS_CHECKPOINT_INTERVAL_MS, LIFETIME_STATS_UPDATE_INTERVAL_MS,
+    LIFETIME_STATS_VERSION,
+};
+use crate::tasks::config_manager::{get_lifetime_stats, set_lifetime_stats, wait_for_config_loaded};
+use crate::tasks::gpio_input::INPUTS;
+use crate::tasks::state_machine::{get_state_machine_state, is_host_powered};
+
+/// Size of the statistics block in flash and in the I2C wire format
+pub const LIFETIME_STATS_SIZE: usize = 32;
+
+/// Counted events
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub enum StatsEvent {
+    /// The system was started (SystemStartup entered)
+    Start,
+    /// Power returned during a blackout before a shutdown was needed
+    BlackoutRiddenThrough,
+    /// A blackout ended in a shutdown
+    BlackoutShutdown,
+    /// The host watchdog expired and the host was power-cycled
+    WatchdogRecovery,
+}
+
+#[derive(Clone, Copy, Default, defmt::Format)]
+struct LifetimeStats {
+    powered_on_s: u32,
+    starts: u32,
+    blackouts_ridden_through: u32,
+    blackouts_shutdown: u32,
+    watchdog_recoveries: u32,
+    energy_j: u64,
+}
+
+impl LifetimeStats {
+    /// Block format: version, 3 reserved bytes, then powered-on seconds, starts,
+    /// blackouts ridden through, blackouts ending in shutdown and watchdog
+    /// recoveries (u32 BE each), and the energy in joules (u64 BE)
+    fn to_bytes(self) -> [u8; LIFETIME_STATS_SIZE] {
+        let mut bytes = [0u8; LIFETIME_STATS_SIZE];
+        bytes[0] = LIFETIME_STATS_VERSION;
+        bytes[4..8].copy_from_slice(&self.powered_on_s.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.starts.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.blackouts_ridden_through.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.blackouts_shutdown.to_be_bytes());
+        bytes[20..24].copy_from_slice(&self.watchdog_recoveries.to_be_bytes());
+        bytes[24..32].copy_from_slice(&self.energy_j.to_be_bytes());
+        bytes
+    }
+
+    /// Parse a stored block. Blocks of another version (or never stored) start from zero.
+    fn from_bytes(bytes: &[u8; LIFETIME_STATS_SIZE]) -> Self {
+        if bytes[0] != LIFETIME_STATS_VERSION {
+            return Self::default();
+        }
+        let u32_at = |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut energy = [0u8; 8];
+        energy.copy_from_slice(&bytes[24..32]);
+        Self {
+            powered_on_s: u32_at(4),
+            starts: u32_at(8),
+            blackouts_ridden_through: u32_at(12),
+            blackouts_shutdown: u32_at(16),
+            watchdog_recoveries: u32_at(20),
+            energy_j: u64::from_be_bytes(energy),
+        }
+    }
+
+    /// Add counts accumulated before the stored statistics were loaded
+    fn add(&mut self, other: &Self) {
+        self.powered_on_s = self.powered_on_s.saturating_add(other.powered_on_s);
+        self.starts = self.starts.saturating_add(other.starts);
+        self.blackouts_ridden_through = self
+            .blackouts_ridden_through
+            .saturating_add(other.blackouts_ridden_through);
+        self.blackouts_shutdown = self.blackouts_shutdown.saturating_add(other.blackouts_shutdown);
+        self.watchdog_recoveries = self.watchdog_recoveries.saturating_add(other.watchdog_recoveries);
+        self.energy_j = self.energy_j.saturating_add(other.energy_j);
+    }
+}
+
+struct StatsState {
+    stats: LifetimeStats,
+    /// Energy below one joule not yet added to the total
+    energy_remainder_j: f32,
+    checkpoint_requested: bool,
+}
+
+static LIFETIME_STATS: Mutex<CriticalSectionRawMutex, StatsState> = Mutex::new(StatsState {
+    stats: LifetimeStats {
+        powered_on_s: 0,
+        starts: 0,
+        blackouts_ridden_through: 0,
+        blackouts_shutdown: 0,
+        watchdog_recoveries: 0,
+        energy_j: 0,
+    },
+    energy_remainder_j: 0.0,
+    checkpoint_requested: false,
+});
+
+/// Count an event. The statistics are checkpointed to flash shortly after.
+pub async fn count_stats_event(event: StatsEvent) {
+    debug!("Lifetime stats event: {}", event);
+    let mut state = LIFETIME_STATS.lock().await;
+    let counter = match event {
+        StatsEvent::Start => &mut state.stats.starts,
+        StatsEvent::BlackoutRiddenThrough => &mut state.stats.blackouts_ridden_through,
+        StatsEvent::BlackoutShutdown => &mut state.stats.blackouts_shutdown,
+        StatsEvent::WatchdogRecovery => &mut state.stats.watchdog_recoveries,
+    };
+    *counter = counter.saturating_add(1);
+    state.checkpoint_requested = true;
+}
+
+/// Checkpoint the statistics to flash at the next update, e.g. before a reset
+pub async fn request_stats_checkpoint() {
+    LIFETIME_STATS.lock().await.checkpoint_requested = true;
+}
+
+/// Statistics block in I2C wire format
+pub async fn get_lifetime_stats_block() -> [u8; LIFETIME_STATS_SIZE] {
+    LIFETIME_STATS.lock().await.stats.to_bytes()
+}
+
+#[task]
+pub async fn lifetime_stats_task() {
+    info!("Starting lifetime statistics task");
+
+    wait_for_config_loaded().await;
+    {
+        let stored = LifetimeStats::from_bytes(&get_lifetime_stats().await);
+        let mut state = LIFETIME_STATS.lock().await;
+        let early = state.stats;
+        state.stats = stored;
+        state.stats.add(&early);
+        info!("Lifetime statistics: {}", state.stats);
+    }
+
+    let interval = Duration::from_millis(LIFETIME_STATS_UPDATE_INTERVAL_MS as u64);
+    let checkpoint_interval = Duration::from_millis(LIFETIME_STATS_CHECKPOINT_INTERVAL_MS as u64);
+    let mut ticker = Ticker::every(interval);
+    let mut last_checkpoint = Instant::now();
+    let mut powered_ms = 0u32;
+
+    loop {
+        ticker.next().await;
+
+        let power_w = {
+            let inputs = INPUTS.lock().await;
+            (inputs.vin * inputs.iin).max(0.0)
+        };
+        let powered = is_host_powered(&get_state_machine_state().await);
+
+        let checkpoint = {
+            let mut state = LIFETIME_STATS.lock().await;
+            state.energy_remainder_j += power_w * interval.as_millis() as f32 / 1000.0;
+            let whole_j = state.energy_remainder_j as u64;
+            state.energy_remainder_j -= whole_j as f32;
+            state.stats.energy_j = state.stats.energy_j.saturating_add(whole_j);
+
+            if powered {
+                powered_ms += LIFETIME_STATS_UPDATE_INTERVAL_MS;
+                state.stats.powered_on_s =
+                    state.stats.powered_on_s.saturating_add(powered_ms / 1000);
+                powered_ms %= 1000;
+            }
+
+            if state.checkpoint_requested || last_checkpoint.elapsed() >= checkpoint_interval {
+                state.checkpoint_requested = false;
+                Some(state.stats.to_bytes())
+            } else {
+                None
+            }
+        };
+
+        if let Some(bytes) = checkpoint {
+            debug!("Checkpointing lifetime statistics");
+            set_lifetime_stats(bytes).await;
+            last_checkpoint = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! CM5 liveness detection for hosts that do not run the daemon.
+//!
+//! The digital input task feeds the LED_ACTIVE and LED_PWR lines of the CM5 into
+//! the detector. In solo mode, the state machine polls it on every tick: if
+//! LED_ACTIVE has not changed for the configured number of minutes, or LED_PWR
+//! keeps blinking as it does when the CM5 firmware reports an error, the CM5 is
+//! considered hung and the configured recovery is applied. The timeout and the
+//! recovery are stored in the config (register 0x1f).
+
+use defmt::warn;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::config::{LIVENESS_PWR_BLINK_EDGES, LIVENESS_PWR_WINDOW_MS};
+use crate::tasks::config_manager::{get_liveness_recovery, get_liveness_timeout_min};
+use crate::tasks::event_log::{LogEventKind, log_event};
+
+/// Size of the liveness status in the I2C wire format
+pub const LIVENESS_STATUS_SIZE: usize = 8;
+
+/// Recovery actions. The discriminant is stored in the config and is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+#[repr(u8)]
+pub enum LivenessRecovery {
+    /// Only record the hang in the event log
+    LogOnly = 0,
+    /// Click the CM5 power button
+    PowerButton = 1,
+    /// Cut the power and restart the system
+    PowerCycle = 2,
+}
+
+impl LivenessRecovery {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::LogOnly),
+            1 => Some(Self::PowerButton),
+            2 => Some(Self::PowerCycle),
+            _ => None,
+        }
+    }
+}
+
+/// Why the CM5 was considered hung. The discriminant is part of the event log payload.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+enum LivenessFault {
+    /// LED_ACTIVE did not change for the configured time
+    ActivityFrozen = 0,
+    /// LED_PWR kept blinking
+    PowerLedBlinking = 1,
+}
+
+struct LivenessState {
+    led_active: bool,
+    led_pwr: bool,
+    /// Last LED_ACTIVE change, or the last restart of the detection
+    last_activity: Instant,
+    pwr_window_start: Instant,
+    /// LED_PWR changes in the current window
+    pwr_edges: u8,
+    /// Whether LED_PWR was blinking in the previous window
+    pwr_blinking: bool,
+    /// Hangs detected since boot
+    detections: u16,
+}
+
+static LIVENESS: Mutex<CriticalSectionRawMutex, LivenessState> = Mutex::new(LivenessState {
+    led_active: false,
+    led_pwr: false,
+    last_activity: Instant::from_ticks(0),
+    pwr_window_start: Instant::from_ticks(0),
+    pwr_edges: 0,
+    pwr_blinking: false,
+    detections: 0,
+});
+
+/// Record a sample of the LED_PWR and LED_ACTIVE lines
+pub async fn record_led_sample(led_pwr: bool, led_active: bool) {
+    let now = Instant::now();
+    let mut state = LIVENESS.lock().await;
+    if led_active != state.led_active {
+        state.led_active = led_active;
+        state.last_activity = now;
+    }
+    if led_pwr != state.led_pwr {
+        state.led_pwr = led_pwr;
+        state.pwr_edges = state.pwr_edges.saturating_add(1);
+    }
+    if now.duration_since(state.pwr_window_start) >= Duration::from_millis(LIVENESS_PWR_WINDOW_MS as u64) {
+        state.pwr_blinking = state.pwr_edges >= LIVENESS_PWR_BLINK_EDGES;
+        state.pwr_edges = 0;
+        state.pwr_window_start = now;
+    }
+}
+
+/// Restart the detection, e.g. when the system enters solo mode
+pub async fn reset_liveness() {
+    let now = Instant::now();
+    let mut state = LIVENESS.lock().await;
+    state.last_activity = now;
+    state.pwr_window_start = now;
+    state.pwr_edges = 0;
+    state.pwr_blinking = false;
+}
+
+/// Recovery to apply if the CM5 looks hung, otherwise None. Called on every state
+/// machine tick in solo mode. A detection restarts the timing, so the recovery is
+/// applied at most once per timeout.
+pub async fn poll_liveness() -> Option<LivenessRecovery> {
+    let timeout_min = get_liveness_timeout_min().await;
+    if timeout_min == 0 {
+        return None;
+    }
+    let fault = {
+        let mut state = LIVENESS.lock().await;
+        let idle = Instant::now().duration_since(state.last_activity);
+        let fault = if state.pwr_blinking {
+            LivenessFault::PowerLedBlinking
+        } else if idle >= Duration::from_secs(timeout_min as u64 * 60) {
+            LivenessFault::ActivityFrozen
+        } else {
+            return None;
+        };
+        state.detections = state.detections.saturating_add(1);
+        fault
+    };
+    reset_liveness().await;
+
+    let recovery =
+        LivenessRecovery::from_u8(get_liveness_recovery().await).unwrap_or(LivenessRecovery::LogOnly);
+    warn!("CM5 liveness lost: {}, recovery {}", fault, recovery);
+    log_event(LogEventKind::LivenessLost, ((fault as u32) << 8) | recovery as u32);
+    Some(recovery)
+}
+
+/// Liveness status in I2C wire format: timeout in minutes (u16 BE, 0 = disabled),
+/// recovery action, seconds since the last LED_ACTIVE change (u16 BE, saturating),
+/// LED_PWR blinking flag and hangs detected since boot (u16 BE)
+pub async fn get_liveness_status() -> [u8; LIVENESS_STATUS_SIZE] {
+    let timeout_min = get_liveness_timeout_min().await;
+    let recovery = get_liveness_recovery().await;
+    let state = LIVENESS.lock().await;
+    let idle_s = Instant::now().duration_since(state.last_activity).as_secs();
+    let mut bytes = [0u8; LIVENESS_STATUS_SIZE];
+    bytes[0..2].copy_from_slice(&timeout_min.to_be_bytes());
+    bytes[2] = recovery;
+    bytes[3..5].copy_from_slice(&(idle_s.min(u16::MAX as u64) as u16).to_be_bytes());
+    bytes[5] = state.pwr_blinking as u8;
+    bytes[6..8].copy_from_slice(&state.detections.to_be_bytes());
+    bytes
+}
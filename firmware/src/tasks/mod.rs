@@ -8,3 +8,18 @@ pub(crate) mod flash_writer;
 pub(crate) mod mark_firmware_booted;
 pub(crate) mod config_manager;
 pub(crate) mod watchdog_feeder;
+pub(crate) mod test_mode;
+pub(crate) mod calibration;
+pub(crate) mod header_gpio;
+pub(crate) mod host_events;
+pub(crate) mod transition_history;
+pub(crate) mod event_log;
+pub(crate) mod shutdown_reason;
+pub(crate) mod lifetime_stats;
+pub(crate) mod telemetry_stats;
+pub(crate) mod telemetry_log;
+pub(crate) mod waveform;
+pub(crate) mod schedule;
+pub(crate) mod host_ping;
+pub(crate) mod liveness;
+pub(crate) mod boot_code;
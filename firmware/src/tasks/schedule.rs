@@ -0,0 +1,152 @@
+//! Weekly power-on and shutdown schedule.
+//!
+//! The schedule is a small table of slots, each with a set of weekdays, a UTC
+//! time of day and an action (power on or graceful shutdown). It is stored in
+//! the config and evaluated on every state machine tick against the software
+//! clock (see `rtc`), so it has no effect until the host has set the time. The
+//! host can inhibit scheduled shutdowns ("stay awake") until the next reset.
+
+use defmt::info;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+
+use crate::config::{SCHEDULE_MAX_CATCH_UP_MINUTES, SCHEDULE_SLOTS};
+use crate::rtc::rtc_epoch_s;
+use crate::tasks::config_manager::{get_schedule, set_schedule};
+
+/// Size of a slot in the config and in the I2C wire format
+pub const SCHEDULE_SLOT_SIZE: usize = 4;
+/// Size of the whole schedule in the config and in the I2C wire format
+pub const SCHEDULE_SIZE: usize = SCHEDULE_SLOTS * SCHEDULE_SLOT_SIZE;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Scheduled actions. The discriminant is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum ScheduleAction {
+    /// Gracefully shut down the CM5 and stay off until the next "on" slot
+    Off = 0,
+    /// Start the system
+    On = 1,
+}
+
+#[derive(Clone, Copy, defmt::Format)]
+pub struct ScheduleSlot {
+    /// Bit 0 = Monday ... bit 6 = Sunday; 0 disables the slot
+    days: u8,
+    action: ScheduleAction,
+    /// Minutes after midnight UTC
+    minute: u16,
+}
+
+impl ScheduleSlot {
+    /// Parse a slot from its wire format: days, action, minute of day (u16 BE).
+    /// Returns None for invalid values.
+    pub fn from_bytes(bytes: [u8; SCHEDULE_SLOT_SIZE]) -> Option<Self> {
+        let action = match bytes[1] {
+            0 => ScheduleAction::Off,
+            1 => ScheduleAction::On,
+            _ => return None,
+        };
+        let minute = u16::from_be_bytes([bytes[2], bytes[3]]);
+        if bytes[0] & 0x80 != 0 || minute >= MINUTES_PER_DAY {
+            return None;
+        }
+        Some(Self {
+            days: bytes[0],
+            action,
+            minute,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; SCHEDULE_SLOT_SIZE] {
+        let minute = self.minute.to_be_bytes();
+        [self.days, self.action as u8, minute[0], minute[1]]
+    }
+
+    /// Whether the slot fires at `epoch_minute` (minutes since the Unix epoch, UTC)
+    fn matches(&self, epoch_minute: u32) -> bool {
+        let day = epoch_minute / MINUTES_PER_DAY as u32;
+        // 1970-01-01 was a Thursday
+        let weekday = (day + 3) % 7;
+        let minute = (epoch_minute % MINUTES_PER_DAY as u32) as u16;
+        self.days & (1 << weekday) != 0 && self.minute == minute
+    }
+}
+
+struct ScheduleState {
+    /// Last minute the schedule was evaluated for
+    last_minute: Option<u32>,
+    stay_awake: bool,
+}
+
+static SCHEDULE_STATE: Mutex<CriticalSectionRawMutex, ScheduleState> =
+    Mutex::new(ScheduleState {
+        last_minute: None,
+        stay_awake: false,
+    });
+
+/// Store slot `index`
+pub async fn set_schedule_slot(index: usize, slot: ScheduleSlot) {
+    let mut schedule = get_schedule().await;
+    let offset = index * SCHEDULE_SLOT_SIZE;
+    schedule[offset..offset + SCHEDULE_SLOT_SIZE].copy_from_slice(&slot.to_bytes());
+    set_schedule(schedule).await;
+}
+
+/// Schedule in I2C wire format: all slots, each days, action and minute of day (u16 BE)
+pub async fn get_schedule_bytes() -> [u8; SCHEDULE_SIZE] {
+    get_schedule().await
+}
+
+/// Inhibit scheduled shutdowns until cleared or until the next reset
+pub async fn set_stay_awake(stay_awake: bool) {
+    info!("Schedule stay awake: {}", stay_awake);
+    SCHEDULE_STATE.lock().await.stay_awake = stay_awake;
+}
+
+pub async fn is_stay_awake() -> bool {
+    SCHEDULE_STATE.lock().await.stay_awake
+}
+
+/// Action of the slots that became due since the previous call, if any. Called on every
+/// state machine tick. An "on" slot takes precedence over an "off" slot due at the same
+/// time; "off" slots are dropped while the stay awake inhibit is set.
+pub async fn poll_schedule() -> Option<ScheduleAction> {
+    let now = rtc_epoch_s()? / 60;
+    let first = {
+        let mut state = SCHEDULE_STATE.lock().await;
+        let previous = state.last_minute.replace(now);
+        match previous {
+            // Evaluate each minute once, including minutes missed during a stall, but
+            // do not replay the schedule after a clock jump
+            Some(previous) if previous < now && now - previous <= SCHEDULE_MAX_CATCH_UP_MINUTES => {
+                previous + 1
+            }
+            Some(_) => return None,
+            // The first minute after the clock is set or restored
+            None => now,
+        }
+    };
+
+    let schedule = get_schedule().await;
+    let mut due = None;
+    for bytes in schedule.chunks_exact(SCHEDULE_SLOT_SIZE) {
+        let Some(slot) = ScheduleSlot::from_bytes(bytes.try_into().unwrap()) else {
+            continue;
+        };
+        if (first..=now).any(|minute| slot.matches(minute)) && due != Some(ScheduleAction::On) {
+            due = Some(slot.action);
+        }
+    }
+
+    if due == Some(ScheduleAction::Off) && is_stay_awake().await {
+        info!("Scheduled shutdown inhibited by stay awake");
+        return None;
+    }
+    if let Some(action) = due {
+        info!("Scheduled action: {}", action);
+    }
+    due
+}
@@ -0,0 +1,151 @@
+//! Shutdown and power-down reason tracking.
+//!
+//! Every transition into ManualShutdown, BlackoutShutdown, PoweredDownManual or
+//! PoweredDownBlackout records a typed reason. The latest record is kept in the
+//! no-init RAM crash record so that it survives the `sys_reset` that follows a
+//! power-down, and is copied to the flash event log. The host reads the reason
+//! for the previous power-down over I2C (register 0x8b) after it boots.
+
+use defmt::info;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::config::POWER_BUTTON_SHUTDOWN_WINDOW_MS;
+use crate::crash_log::{get_reset_report, record_shutdown_in_noinit};
+use crate::tasks::event_log::{LogEventKind, log_event};
+
+/// Shutdown reasons. The discriminant is part of the I2C API and the event log format.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum ShutdownReason {
+    Unknown = 0,
+    /// The solo mode blackout timeout expired
+    BlackoutTimeout = 1,
+    /// The host requested a shutdown during a co-op mode blackout
+    BlackoutHostRequest = 2,
+    /// The host watchdog expired
+    HostWatchdog = 3,
+    /// The host requested a shutdown over I2C
+    HostRequest = 4,
+    /// The CM5 shut down after the power button was pressed
+    PowerButton = 5,
+    /// The CM5 shut down on its own
+    ComputeModuleOff = 6,
+    /// Forced power off over I2C
+    OffCommand = 7,
+    /// The CM5 shut down while the supercap overvoltage alarm was active
+    SupercapOvervoltage = 8,
+    /// An "off" slot of the schedule
+    Schedule = 9,
+    /// The CM5 looked hung in solo mode (see `tasks::liveness`)
+    Liveness = 10,
+}
+
+impl ShutdownReason {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::BlackoutTimeout,
+            2 => Self::BlackoutHostRequest,
+            3 => Self::HostWatchdog,
+            4 => Self::HostRequest,
+            5 => Self::PowerButton,
+            6 => Self::ComputeModuleOff,
+            7 => Self::OffCommand,
+            8 => Self::SupercapOvervoltage,
+            9 => Self::Schedule,
+            10 => Self::Liveness,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct ShutdownRecord {
+    pub reason: ShutdownReason,
+    /// State number of the shutdown or powered-down state that was entered
+    pub state: u8,
+    /// The CM5 powered itself off before its power was cut
+    pub graceful: bool,
+}
+
+impl ShutdownRecord {
+    /// Packed representation in the no-init record and the event log payload:
+    /// reason in bits 0-7, state in bits 8-15, graceful flag in bit 16
+    pub fn to_u32(self) -> u32 {
+        (self.reason as u32) | ((self.state as u32) << 8) | ((self.graceful as u32) << 16)
+    }
+
+    pub fn from_u32(value: u32) -> Self {
+        Self {
+            reason: ShutdownReason::from_u8(value as u8),
+            state: (value >> 8) as u8,
+            graceful: value & (1 << 16) != 0,
+        }
+    }
+
+    /// I2C wire format: reason, state, graceful flag
+    fn to_bytes(self) -> [u8; 3] {
+        [self.reason as u8, self.state, self.graceful as u8]
+    }
+}
+
+struct ShutdownState {
+    /// Latest shutdown record of the current run
+    current: Option<ShutdownRecord>,
+    last_power_button_press: Option<Instant>,
+}
+
+static SHUTDOWN_STATE: Mutex<CriticalSectionRawMutex, ShutdownState> =
+    Mutex::new(ShutdownState {
+        current: None,
+        last_power_button_press: None,
+    });
+
+/// Remember a power button press, to attribute a following CM5 shutdown to it
+pub async fn note_power_button_press() {
+    SHUTDOWN_STATE.lock().await.last_power_button_press = Some(Instant::now());
+}
+
+/// Whether the power button was pressed shortly before now
+pub async fn power_button_pressed_recently() -> bool {
+    SHUTDOWN_STATE
+        .lock()
+        .await
+        .last_power_button_press
+        .is_some_and(|pressed| {
+            pressed.elapsed() < Duration::from_millis(POWER_BUTTON_SHUTDOWN_WINDOW_MS as u64)
+        })
+}
+
+/// Reason of the latest shutdown of the current run, if any
+pub async fn current_shutdown_reason() -> Option<ShutdownReason> {
+    SHUTDOWN_STATE
+        .lock()
+        .await
+        .current
+        .map(|record| record.reason)
+}
+
+/// Record a shutdown transition in RAM, no-init RAM and the event log
+pub async fn record_shutdown(record: ShutdownRecord) {
+    info!("Shutdown reason: {}", record);
+    SHUTDOWN_STATE.lock().await.current = Some(record);
+    record_shutdown_in_noinit(record.to_u32());
+    log_event(LogEventKind::Shutdown, record.to_u32());
+}
+
+/// Shutdown report in I2C wire format: the last shutdown record of the previous run,
+/// then the last one of the current run (reason, state, graceful flag each). Reason
+/// 0 with state 0 means no shutdown was recorded.
+pub async fn get_shutdown_report() -> [u8; 6] {
+    let mut bytes = [0u8; 6];
+    let previous = get_reset_report().await.shutdown;
+    if previous != 0 {
+        bytes[0..3].copy_from_slice(&ShutdownRecord::from_u32(previous).to_bytes());
+    }
+    if let Some(current) = SHUTDOWN_STATE.lock().await.current {
+        bytes[3..6].copy_from_slice(&current.to_bytes());
+    }
+    bytes
+}
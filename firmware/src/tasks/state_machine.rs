@@ -1,10 +1,13 @@
-use crate::led_patterns::{get_state_pattern, get_vscap_alarm_pattern};
-use crate::tasks::config_manager::{get_auto_restart, get_shutdown_wait_duration_ms, get_solo_depleting_timeout_ms, get_vscap_power_on_threshold, usb_power_on, usb_power_off};
+use crate::led_patterns::{get_boot_code_pattern, get_state_pattern, get_vscap_alarm_pattern};
+use crate::tasks::config_manager::{get_auto_restart, get_boot_watchdog_timeout_s, get_shutdown_wait_duration_ms, get_solo_depleting_timeout_ms, get_vscap_power_on_threshold, get_watchdog_timeout_ms, usb_power_on, usb_power_off};
 use crate::tasks::led_blinker::{LED_BLINKER_EVENT_CHANNEL, LEDBlinkerEvents};
 use crate::tasks::power_button::{POWER_BUTTON_EVENT_CHANNEL, PowerButtonEvents};
+use crate::tasks::test_mode::{
+    TEST_OUTPUT_EN_3V3, TEST_OUTPUT_EN_5V, TEST_OUTPUT_PCIE_SLEEP, is_test_mode_requested,
+    request_test_run,
+};
 use alloc::vec::Vec;
 use core::fmt::Debug;
-use cortex_m::peripheral::SCB;
 use defmt::*;
 use embassy_executor::task;
 use embassy_rp::gpio::{Level, Output};
@@ -12,12 +15,25 @@ use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
 use embassy_sync::channel;
 use embassy_sync::mutex::Mutex;
 use embassy_sync::once_lock::OnceLock;
-use embassy_time::{Duration, Instant, Ticker};
+use embassy_time::{Duration, Instant, with_deadline};
 use statig::prelude::*;
 
 use crate::config::*;
+use crate::crash_log::software_reset;
 use crate::config_resources::StateMachineOutputResources;
+use crate::tasks::boot_code::{BootCode, start_boot_code_decoding, take_boot_code};
 use crate::tasks::gpio_input::INPUTS;
+use crate::tasks::host_events::{HostEvent, post_host_event, post_host_event_with_payload};
+use crate::tasks::event_log::{LogEventKind, log_event};
+use crate::tasks::shutdown_reason::{
+    ShutdownReason, ShutdownRecord, current_shutdown_reason, power_button_pressed_recently,
+    record_shutdown,
+};
+use crate::tasks::liveness::{LivenessRecovery, poll_liveness, reset_liveness};
+use crate::tasks::lifetime_stats::{StatsEvent, count_stats_event, request_stats_checkpoint};
+use crate::tasks::schedule::{ScheduleAction, poll_schedule};
+use crate::tasks::transition_history::{TransitionRecord, record_transition};
+use crate::tasks::waveform::trigger_waveform_capture;
 
 use super::led_blinker::LEDBlinkerChannelType;
 use super::power_button::PowerButtonChannelType;
@@ -30,6 +46,20 @@ async fn is_vin_power_available() -> bool {
     vin_power
 }
 
+/// Convert an external event to the internal state machine event
+fn to_internal_event(event: StateMachineEvents) -> Event {
+    match event {
+        StateMachineEvents::Shutdown => Event::Shutdown,
+        StateMachineEvents::SetHostWatchdogTimeout(timeout) => Event::SetWatchdogTimeout(timeout),
+        StateMachineEvents::HostWatchdogPing => Event::WatchdogPing,
+        StateMachineEvents::StandbyShutdown => Event::StandbyShutdown,
+        StateMachineEvents::Off => Event::Off,
+        StateMachineEvents::PowerButtonPress => Event::PowerButtonPress,
+        StateMachineEvents::SetTestOutputs(bits) => Event::SetTestOutputs(bits),
+        StateMachineEvents::VinLost => Event::VinLost,
+    }
+}
+
 /// Helper function to check vscap voltage and return (voltage, is_above_threshold)
 async fn get_vscap_status() -> (f32, bool) {
     let inputs = INPUTS.lock().await;
@@ -55,6 +85,10 @@ pub enum StateMachineEvents {
     HostWatchdogPing,
     /// Physical power button was pressed
     PowerButtonPress,
+    /// Set the power outputs directly (test mode only, see `tasks::test_mode`)
+    SetTestOutputs(u8),
+    /// VIN dropped below the power threshold (fast path from the analog input task)
+    VinLost,
 }
 
 pub type StateMachineChannelType =
@@ -91,6 +125,16 @@ pub enum Event {
     WatchdogPing,
     /// Physical power button was pressed
     PowerButtonPress,
+    /// Set the power outputs directly (test mode only)
+    SetTestOutputs(u8),
+    /// VIN dropped below the power threshold, detected without waiting for a Tick
+    VinLost,
+    /// An "on" slot of the schedule is due
+    ScheduledOn,
+    /// An "off" slot of the schedule is due and shutdowns are not inhibited
+    ScheduledOff,
+    /// The CM5 looks hung in solo mode; apply the recovery
+    LivenessLost(LivenessRecovery),
 }
 
 /// GPIO outputs that are controlled by the state machine task.
@@ -120,6 +164,13 @@ impl Outputs {
         self.en_3v3.set_high(); // Active-low, so High = disabled
         self.pcie_sleep.set_high();
     }
+
+    /// Set each output from a bitfield of asserted outputs (test mode only)
+    fn set_test_outputs(&mut self, bits: u8) {
+        self.en_5v.set_level(Level::from((bits & TEST_OUTPUT_EN_5V) != 0));
+        self.en_3v3.set_level(Level::from((bits & TEST_OUTPUT_EN_3V3) == 0)); // Active-low
+        self.pcie_sleep.set_level(Level::from((bits & TEST_OUTPUT_PCIE_SLEEP) != 0));
+    }
 }
 
 pub struct Context {
@@ -128,6 +179,8 @@ pub struct Context {
     pub led_blinker_channel: &'static LEDBlinkerChannelType,
     pub host_watchdog_timeout_ms: u16,
     pub host_watchdog_last_ping: Instant,
+    /// Deadline for the first host ping after boot in milliseconds (0 = not pending)
+    pub boot_watchdog_timeout_ms: u32,
     pub vscap_alarm_active: bool,
 }
 
@@ -144,10 +197,20 @@ impl Context {
             led_blinker_channel,
             host_watchdog_timeout_ms,
             host_watchdog_last_ping: Instant::now(),
+            boot_watchdog_timeout_ms: 0,
             vscap_alarm_active: false,
         }
     }
 
+    /// Time allowed since the last host watchdog ping in milliseconds (0 = disabled)
+    fn host_watchdog_deadline_ms(&self) -> u32 {
+        if self.boot_watchdog_timeout_ms > 0 {
+            self.boot_watchdog_timeout_ms
+        } else {
+            self.host_watchdog_timeout_ms as u32
+        }
+    }
+
     async fn set_led_pattern(&self, state: &State) {
         let _ = self
             .led_blinker_channel
@@ -162,6 +225,16 @@ impl Context {
             .await;
     }
 
+    async fn set_boot_code_led_pattern(&self, code: BootCode) {
+        let _ = self
+            .led_blinker_channel
+            .send(LEDBlinkerEvents::SetPattern(get_boot_code_pattern(
+                code.long_flashes,
+                code.short_flashes,
+            )))
+            .await;
+    }
+
     async fn send_power_button_event(&self, event: PowerButtonEvents) {
         let _ = self.power_button_channel.send(event).await;
     }
@@ -173,6 +246,148 @@ pub async fn get_state_machine_state() -> State {
     *STATE_MACHINE_STATE.get().await.lock().await
 }
 
+/// Whether the CM5 is powered in the given state
+pub fn is_host_powered(state: &State) -> bool {
+    matches!(
+        state,
+        State::SystemStartup {}
+            | State::OperationalSolo {}
+            | State::OperationalCoOp {}
+            | State::BlackoutSolo { .. }
+            | State::BlackoutCoOp { .. }
+            | State::BlackoutShutdown { .. }
+            | State::ManualShutdown { .. }
+            | State::HostUnresponsive { .. }
+            | State::EnteringStandby { .. }
+    )
+}
+
+/// Timing information published by the state machine task for introspection
+struct StateTiming {
+    /// When the current state was entered, for states without an `entry_time`
+    entered: Instant,
+    /// Host watchdog timeout after the first ping (0 = disabled)
+    host_watchdog_timeout_ms: u16,
+    host_watchdog_deadline_ms: u32,
+    host_watchdog_last_ping: Instant,
+}
+
+static STATE_TIMING: Mutex<CriticalSectionRawMutex, StateTiming> = Mutex::new(StateTiming {
+    entered: Instant::from_ticks(0),
+    host_watchdog_timeout_ms: 0,
+    host_watchdog_deadline_ms: 0,
+    host_watchdog_last_ping: Instant::from_ticks(0),
+});
+
+/// Size of the state timing block in the I2C wire format
+pub const STATE_TIMING_SIZE: usize = 21;
+
+/// Value reported for a timeout that is not pending
+const TIMEOUT_NOT_PENDING: u32 = u32::MAX;
+
+fn entry_time(state: &State) -> Option<Instant> {
+    match state {
+        State::BlackoutSolo { entry_time }
+        | State::BlackoutCoOp { entry_time }
+        | State::BlackoutShutdown { entry_time }
+        | State::ManualShutdown { entry_time }
+        | State::PoweredDownBlackout { entry_time }
+        | State::PoweredDownManual { entry_time }
+        | State::HostUnresponsive { entry_time }
+        | State::EnteringStandby { entry_time } => Some(*entry_time),
+        _ => None,
+    }
+}
+
+fn millis_u32(duration: Duration) -> u32 {
+    duration.as_millis().min(u32::MAX as u64) as u32
+}
+
+/// Time remaining until `start + timeout_ms`, saturating at zero
+fn remaining_ms(start: Instant, timeout_ms: u32) -> u32 {
+    let elapsed = millis_u32(Instant::now().duration_since(start));
+    timeout_ms.saturating_sub(elapsed)
+}
+
+/// Host watchdog timeout in ms as applied by the state machine (0 = disabled). While the
+/// boot watchdog waits for the first ping, this is the timeout that applies after it.
+pub async fn get_host_watchdog_timeout_ms() -> u16 {
+    STATE_TIMING.lock().await.host_watchdog_timeout_ms
+}
+
+/// Time since the last host watchdog ping in ms, or 0 if the watchdog is not running
+pub async fn get_host_watchdog_elapsed_ms() -> u32 {
+    let timing = STATE_TIMING.lock().await;
+    if timing.host_watchdog_deadline_ms == 0 {
+        return 0;
+    }
+    millis_u32(Instant::now().duration_since(timing.host_watchdog_last_ping))
+}
+
+/// State timing in I2C wire format: state number, time in state (ms), then the time
+/// remaining before the host watchdog (or the forced reboot once it expired), solo
+/// depleting, shutdown wait and off-state timeouts fire (ms). All values are u32
+/// big-endian; timeouts that are not pending in the current state read as 0xFFFFFFFF.
+pub async fn get_state_timing() -> [u8; STATE_TIMING_SIZE] {
+    let state = get_state_machine_state().await;
+    let (entered, watchdog_deadline_ms, watchdog_last_ping) = {
+        let timing = STATE_TIMING.lock().await;
+        (
+            timing.entered,
+            timing.host_watchdog_deadline_ms,
+            timing.host_watchdog_last_ping,
+        )
+    };
+    let entered = entry_time(&state).unwrap_or(entered);
+
+    let mut host_watchdog = TIMEOUT_NOT_PENDING;
+    let mut solo_depleting = TIMEOUT_NOT_PENDING;
+    let mut shutdown_wait = TIMEOUT_NOT_PENDING;
+    let mut off_state = TIMEOUT_NOT_PENDING;
+    match state {
+        State::OperationalCoOp {} if watchdog_deadline_ms > 0 => {
+            host_watchdog = remaining_ms(watchdog_last_ping, watchdog_deadline_ms);
+        }
+        State::BlackoutSolo { .. } => {
+            solo_depleting = remaining_ms(entered, get_solo_depleting_timeout_ms().await);
+        }
+        State::ManualShutdown { .. }
+        | State::BlackoutShutdown { .. }
+        | State::EnteringStandby { .. } => {
+            shutdown_wait = remaining_ms(entered, get_shutdown_wait_duration_ms().await);
+        }
+        // The forced reboot countdown that follows an expired host watchdog
+        State::HostUnresponsive { .. } => {
+            host_watchdog = remaining_ms(entered, HOST_WATCHDOG_REBOOT_DURATION_MS);
+        }
+        State::PoweredDownBlackout { .. } => {
+            off_state = remaining_ms(entered, OFF_STATE_DURATION_MS);
+        }
+        // Auto restart does not apply after a scheduled shutdown
+        State::PoweredDownManual { .. }
+            if get_auto_restart().await
+                && current_shutdown_reason().await != Some(ShutdownReason::Schedule) =>
+        {
+            off_state = remaining_ms(entered, OFF_STATE_DURATION_MS);
+        }
+        _ => {}
+    }
+
+    let mut bytes = [0u8; STATE_TIMING_SIZE];
+    bytes[0] = state_as_u8(&state);
+    let values = [
+        millis_u32(Instant::now().duration_since(entered)),
+        host_watchdog,
+        solo_depleting,
+        shutdown_wait,
+        off_state,
+    ];
+    for (i, value) in values.iter().enumerate() {
+        bytes[1 + 4 * i..5 + 4 * i].copy_from_slice(&value.to_be_bytes());
+    }
+    bytes
+}
+
 pub fn state_as_str(state: &State) -> &'static str {
     match state {
         State::PowerOff {} => "PowerOff",
@@ -189,6 +404,7 @@ pub fn state_as_str(state: &State) -> &'static str {
         State::HostUnresponsive { .. } => "HostUnresponsive",
         State::EnteringStandby { .. } => "EnteringStandby",
         State::Standby {} => "Standby",
+        State::TestMode {} => "TestMode",
     }
 }
 
@@ -210,11 +426,191 @@ pub fn state_as_u8(state: &State) -> u8 {
         State::HostUnresponsive { .. } => 11,
         State::EnteringStandby { .. } => 12,
         State::Standby {} => 13,
+        State::TestMode {} => 14,
+    }
+}
+
+pub fn event_as_u8(event: &Event) -> u8 {
+    // Note: the event numbering is part of the I2C API (transition history).
+    // Any new events must be added with a unique number.
+    match event {
+        Event::Tick => 0,
+        Event::SupercapOvervoltage => 1,
+        Event::ComputeModuleOn => 2,
+        Event::ComputeModuleOff => 3,
+        Event::Shutdown => 4,
+        Event::StandbyShutdown => 5,
+        Event::Off => 6,
+        Event::SetWatchdogTimeout(_) => 7,
+        Event::WatchdogPing => 8,
+        Event::PowerButtonPress => 9,
+        Event::SetTestOutputs(_) => 10,
+        Event::VinLost => 11,
+        Event::ScheduledOn => 12,
+        Event::ScheduledOff => 13,
+        Event::LivenessLost(_) => 14,
+    }
+}
+
+/// Record the state after handling `event`, and log the transition if the state changed
+pub async fn record_state_machine_state(state: &State, event: &Event, context: &Context) {
+    let previous = {
+        let mut recorded = STATE_MACHINE_STATE.get().await.lock().await;
+        core::mem::replace(&mut *recorded, *state)
+    };
+    let changed = state_as_u8(&previous) != state_as_u8(state);
+    {
+        let mut timing = STATE_TIMING.lock().await;
+        if changed {
+            timing.entered = Instant::now();
+        }
+        timing.host_watchdog_timeout_ms = context.host_watchdog_timeout_ms;
+        timing.host_watchdog_deadline_ms = context.host_watchdog_deadline_ms();
+        timing.host_watchdog_last_ping = context.host_watchdog_last_ping;
+    }
+    if changed {
+        let (vin, vscap) = {
+            let inputs = INPUTS.lock().await;
+            (inputs.vin, inputs.vscap)
+        };
+        record_transition(TransitionRecord::new(
+            state_as_u8(&previous),
+            state_as_u8(state),
+            event_as_u8(event),
+            vin,
+            vscap,
+        ))
+        .await;
+        if let Some(reason) = shutdown_reason(&previous, state, event, context).await {
+            record_shutdown(ShutdownRecord {
+                reason,
+                state: state_as_u8(state),
+                graceful: matches!(event, Event::ComputeModuleOff),
+            })
+            .await;
+        }
+        count_transition_stats(&previous, state).await;
+        post_transition_events(&previous, state).await;
+    }
+}
+
+/// Update the lifetime statistics for a state change
+async fn count_transition_stats(from: &State, to: &State) {
+    let is_blackout = |state: &State| {
+        matches!(state, State::BlackoutSolo { .. } | State::BlackoutCoOp { .. })
+    };
+    match to {
+        State::SystemStartup {} => count_stats_event(StatsEvent::Start).await,
+        State::OperationalSolo {} | State::OperationalCoOp {} if is_blackout(from) => {
+            count_stats_event(StatsEvent::BlackoutRiddenThrough).await
+        }
+        State::BlackoutShutdown { .. } => count_stats_event(StatsEvent::BlackoutShutdown).await,
+        State::HostUnresponsive { .. } => count_stats_event(StatsEvent::WatchdogRecovery).await,
+        // Powered-down states are followed by a reset
+        State::PoweredDownManual { .. } | State::PoweredDownBlackout { .. } => {
+            request_stats_checkpoint().await
+        }
+        _ => {}
+    }
+}
+
+/// Reason for a transition into a shutdown or powered-down state, or None for other transitions
+async fn shutdown_reason(
+    from: &State,
+    to: &State,
+    event: &Event,
+    context: &Context,
+) -> Option<ShutdownReason> {
+    let reason = match (to, event) {
+        (State::ManualShutdown { .. }, Event::ScheduledOff) => ShutdownReason::Schedule,
+        (State::ManualShutdown { .. }, _) => ShutdownReason::HostRequest,
+        (State::BlackoutShutdown { .. }, Event::Shutdown) => ShutdownReason::BlackoutHostRequest,
+        (State::BlackoutShutdown { .. }, _) => ShutdownReason::BlackoutTimeout,
+        (State::PoweredDownManual { .. } | State::PoweredDownBlackout { .. }, _) => match from {
+            // Completion of a shutdown keeps the reason the shutdown was started for
+            State::ManualShutdown { .. } | State::BlackoutShutdown { .. } => {
+                current_shutdown_reason().await.unwrap_or(ShutdownReason::Unknown)
+            }
+            // The forced reboot once the countdown expires
+            State::HostUnresponsive { .. }
+                if matches!(to, State::PoweredDownBlackout { .. })
+                    && matches!(event, Event::Tick) =>
+            {
+                ShutdownReason::HostWatchdog
+            }
+            _ => match event {
+                Event::Off => ShutdownReason::OffCommand,
+                Event::ComputeModuleOff if power_button_pressed_recently().await => {
+                    ShutdownReason::PowerButton
+                }
+                Event::ComputeModuleOff if context.vscap_alarm_active => {
+                    ShutdownReason::SupercapOvervoltage
+                }
+                Event::ComputeModuleOff => ShutdownReason::ComputeModuleOff,
+                Event::LivenessLost(_) => ShutdownReason::Liveness,
+                _ => ShutdownReason::Unknown,
+            },
+        },
+        _ => return None,
+    };
+    Some(reason)
+}
+
+/// Report a state change and the events it implies to the host
+async fn post_transition_events(from: &State, to: &State) {
+    let is_blackout = |state: &State| {
+        matches!(state, State::BlackoutSolo { .. } | State::BlackoutCoOp { .. })
+    };
+    if is_blackout(to) {
+        if matches!(from, State::OperationalSolo {} | State::OperationalCoOp {}) {
+            trigger_waveform_capture().await;
+        }
+        let (vscap, _) = get_vscap_status().await;
+        log_event(LogEventKind::Blackout, (vscap * 1000.0) as u32);
+        post_host_event_with_payload(HostEvent::Blackout, (vscap * 1000.0) as u32).await;
+    } else if is_blackout(from)
+        && matches!(to, State::OperationalSolo {} | State::OperationalCoOp {})
+    {
+        let vin = INPUTS.lock().await.vin;
+        post_host_event_with_payload(HostEvent::PowerRestored, (vin * 1000.0) as u32).await;
+    }
+    if matches!(to, State::HostUnresponsive { .. }) {
+        log_event(LogEventKind::WatchdogTimeout, 0);
+        post_host_event(HostEvent::WatchdogTimeout).await;
+    }
+    let transition = ((state_as_u8(from) as u32) << 8) | state_as_u8(to) as u32;
+    post_host_event_with_payload(HostEvent::StateChange, transition).await;
+}
+
+/// Operational state to enter once the CM5 is on. With the boot watchdog enabled, the
+/// system starts in co-op mode and the host must send its first ping within the boot
+/// deadline; later pings are held to the stored watchdog timeout.
+async fn booted_state(context: &mut Context) -> State {
+    let boot_timeout_s = get_boot_watchdog_timeout_s().await;
+    if boot_timeout_s == 0 {
+        return State::operational_solo(); // Start in solo mode
     }
+    let boot_timeout_s = boot_timeout_s.max(MIN_BOOT_WATCHDOG_TIMEOUT_S);
+    info!("Boot watchdog enabled, first ping due in {} s", boot_timeout_s);
+    let timeout_ms = get_watchdog_timeout_ms().await;
+    context.host_watchdog_timeout_ms = if timeout_ms > 0 {
+        timeout_ms
+    } else {
+        HOST_WATCHDOG_DEFAULT_TIMEOUT_MS
+    };
+    context.boot_watchdog_timeout_ms = boot_timeout_s as u32 * 1000;
+    context.host_watchdog_last_ping = Instant::now();
+    State::operational_co_op()
 }
 
-pub async fn record_state_machine_state(state: &State) {
-    *STATE_MACHINE_STATE.get().await.lock().await = *state;
+/// Blackout state to enter from co-op mode. Until the first ping of the boot watchdog
+/// there is no host that would shut down, so the solo depleting timeout applies.
+fn blackout_state(context: &Context) -> State {
+    if context.boot_watchdog_timeout_ms > 0 {
+        State::blackout_solo(Instant::now())
+    } else {
+        State::blackout_co_op(Instant::now())
+    }
 }
 
 #[derive(Debug, Default)]
@@ -246,7 +642,9 @@ pub struct HalpiStateMachine {}
 /// ├── HostUnresponsive (host watchdog timeout)
 /// │   ├── WatchdogPing ──> Operational(cooperative)
 /// │   └── Timeout ──> PoweredDownBlackout
-/// └── EnteringStandby ──ComputeModuleOff──> Standby ──ComputeModuleOn──> Operational(solo)
+/// └── EnteringStandby ──ComputeModuleOff──> Standby ──ComputeModuleOn──> Operational(solo, or co-op with the boot watchdog)
+///
+/// PowerOff ──(TEST_MODE asserted at boot)──> TestMode
 ///
 /// PoweredDownBlackout ──[always restart after timeout]──> System Reset
 /// PoweredDownManual ──[restart if auto_restart enabled]──> System Reset
@@ -305,12 +703,19 @@ impl HalpiStateMachine {
     /// - PCIe in sleep mode
     ///
     /// Transitions:
+    /// - Tick (when TEST_MODE was asserted at boot) -> TestMode
     /// - Tick (when VIN > threshold) -> OffCharging (external power applied)
+    ///
+    /// Scheduled "on" slots need no handling here: the system starts by itself
+    /// as soon as external power is available.
     #[allow(unused_variables)]
     #[state(entry_action = "enter_power_off")]
     async fn power_off(&mut self, event: &Event, context: &mut Context) -> Outcome<State> {
         match event {
             Event::Tick => {
+                if is_test_mode_requested() {
+                    return Transition(State::test_mode());
+                }
                 // Check if external power is available
                 if is_vin_power_available().await {
                     Transition(State::off_charging())
@@ -378,12 +783,13 @@ impl HalpiStateMachine {
     /// - LED shows boot pattern
     ///
     /// Transitions:
-    /// - ComputeModuleOn -> Operational(solo) (CM5 successfully powered up)
+    /// - ComputeModuleOn -> Operational(solo), or Operational(co-op) if the boot watchdog
+    ///   is enabled (CM5 successfully powered up)
     /// - Tick (when VIN <= threshold) -> PowerOff (power lost during boot)
     #[state(entry_action = "enter_system_startup")]
-    async fn system_startup(event: &Event) -> Outcome<State> {
+    async fn system_startup(event: &Event, context: &mut Context) -> Outcome<State> {
         match event {
-            Event::ComputeModuleOn => Transition(State::operational_solo()), // Start in solo mode
+            Event::ComputeModuleOn => Transition(booted_state(context).await),
             Event::Tick => {
                 // Check if external power is still available
                 if !is_vin_power_available().await {
@@ -401,6 +807,7 @@ impl HalpiStateMachine {
         context.outputs.power_on();
         usb_power_on().await;
         context.set_led_pattern(&State::system_startup()).await;
+        start_boot_code_decoding().await;
     }
 
     /// Superstate for all situations where the system is powered on and running
@@ -420,6 +827,7 @@ impl HalpiStateMachine {
             Event::Off => Transition(State::powered_down_manual(Instant::now())), // Force immediate shutdown - command-based
             Event::WatchdogPing => {
                 context.host_watchdog_last_ping = Instant::now();
+                context.boot_watchdog_timeout_ms = 0; // The host is up
                 Handled
             }
             Event::SupercapOvervoltage => {
@@ -436,7 +844,7 @@ impl HalpiStateMachine {
     /// Superstate for operational modes (solo and cooperative)
     ///
     /// Handles common operational logic:
-    /// - Shutdown requests for graceful shutdown
+    /// - Shutdown requests and scheduled "off" slots for graceful shutdown
     /// - StandbyShutdown requests for low power mode
     /// - ExternalPowerOff events that trigger blackout transitions
     ///
@@ -446,6 +854,14 @@ impl HalpiStateMachine {
     async fn operational(event: &Event, context: &mut Context) -> Outcome<State> {
         match event {
             Event::Shutdown => Transition(State::manual_shutdown(Instant::now())), // Graceful shutdown from operational mode
+            Event::ScheduledOff => {
+                // Unlike a host request, nothing has told the CM5 to shut down yet
+                post_host_event(HostEvent::ScheduledShutdown).await;
+                context
+                    .send_power_button_event(PowerButtonEvents::DoubleClick)
+                    .await;
+                Transition(State::manual_shutdown(Instant::now()))
+            }
             Event::StandbyShutdown => Transition(State::entering_standby(Instant::now())),
             _ => Super,
         }
@@ -463,8 +879,10 @@ impl HalpiStateMachine {
     /// - Host watchdog is disabled
     ///
     /// Transitions:
-    /// - Tick (when VIN <= threshold) -> BlackoutSolo (external power lost, running on supercap)
+    /// - Tick (when VIN <= threshold), VinLost -> BlackoutSolo (external power lost, running on supercap)
     /// - SetWatchdogTimeout(>0) -> OperationalCoOp (enable cooperative mode)
+    /// - LivenessLost -> PoweredDownBlackout (CM5 hung, power cycle), or a power button
+    ///   click or nothing depending on the configured recovery
     /// - StandbyShutdown -> EnteringStandby (low power mode request) [handled by superstate]
     #[allow(unused_variables)]
     #[state(superstate = "operational", entry_action = "enter_operational_solo")]
@@ -478,6 +896,7 @@ impl HalpiStateMachine {
                     Super
                 }
             }
+            Event::VinLost => Transition(State::blackout_solo(Instant::now())),
             Event::SetWatchdogTimeout(timeout) => {
                 if *timeout > 0 {
                     context.host_watchdog_timeout_ms = *timeout;
@@ -487,6 +906,18 @@ impl HalpiStateMachine {
                     Super
                 }
             }
+            Event::LivenessLost(recovery) => match recovery {
+                LivenessRecovery::LogOnly => Handled,
+                LivenessRecovery::PowerButton => {
+                    context
+                        .send_power_button_event(PowerButtonEvents::Click)
+                        .await;
+                    Handled
+                }
+                LivenessRecovery::PowerCycle => {
+                    Transition(State::powered_down_blackout(Instant::now()))
+                }
+            },
             _ => Super,
         }
     }
@@ -495,6 +926,8 @@ impl HalpiStateMachine {
     async fn enter_operational_solo(context: &mut Context) {
         context.set_led_pattern(&State::operational_solo()).await;
         context.host_watchdog_timeout_ms = 0; // Disable watchdog
+        context.boot_watchdog_timeout_ms = 0;
+        reset_liveness().await;
     }
 
     /// System is fully operational in cooperative mode
@@ -509,8 +942,10 @@ impl HalpiStateMachine {
     /// - Host watchdog timeout monitoring active
     ///
     /// Transitions:
-    /// - Tick (when VIN <= threshold) -> BlackoutCoOp (external power lost, running on supercap)
-    /// - Tick (watchdog timeout) -> HostUnresponsive (host stopped responding)
+    /// - Tick (when VIN <= threshold), VinLost -> BlackoutCoOp (external power lost, running on supercap),
+    ///   or BlackoutSolo while the boot watchdog waits for the first ping (no host to shut down)
+    /// - Tick (watchdog timeout) -> HostUnresponsive (host stopped responding, or did not
+    ///   send the first ping within the boot watchdog deadline)
     /// - SetWatchdogTimeout(0) -> OperationalSolo (disable cooperative mode)
     /// - StandbyShutdown -> EnteringStandby (low power mode request) [handled by superstate]
     #[allow(unused_variables)]
@@ -520,22 +955,24 @@ impl HalpiStateMachine {
             Event::Tick => {
                 // Check if external power is still available
                 if !is_vin_power_available().await {
-                    return Transition(State::blackout_co_op(Instant::now()));
+                    return Transition(blackout_state(context));
                 }
 
                 if Instant::now().duration_since(context.host_watchdog_last_ping)
-                    > Duration::from_millis(context.host_watchdog_timeout_ms as u64)
+                    > Duration::from_millis(context.host_watchdog_deadline_ms() as u64)
                 {
                     return Transition(State::host_unresponsive(Instant::now()));
                 }
                 Super
             }
+            Event::VinLost => Transition(blackout_state(context)),
             Event::SetWatchdogTimeout(timeout) => {
                 if *timeout == 0 {
                     context.host_watchdog_timeout_ms = 0;
                     Transition(State::operational_solo())
                 } else {
                     context.host_watchdog_timeout_ms = *timeout;
+                    context.boot_watchdog_timeout_ms = 0;
                     Super
                 }
             }
@@ -569,7 +1006,8 @@ impl HalpiStateMachine {
     /// - Limited runtime based on supercap charge and power consumption
     ///
     /// Transitions:
-    /// - Tick (when VIN > threshold) -> OperationalSolo (external power restored)
+    /// - Tick (when VIN > threshold) -> OperationalSolo (external power restored), or
+    ///   OperationalCoOp if the blackout started while the boot watchdog was running
     /// - Tick (timeout) -> BlackoutShutdown (automatic shutdown after timeout)
     #[allow(unused_variables)]
     #[state(superstate = "blackout", entry_action = "enter_blackout_solo")]
@@ -578,6 +1016,10 @@ impl HalpiStateMachine {
             Event::Tick => {
                 // Check if external power has been restored
                 if is_vin_power_available().await {
+                    // A blackout during the boot watchdog returns to co-op mode
+                    if context.host_watchdog_timeout_ms > 0 {
+                        return Transition(State::operational_co_op());
+                    }
                     return Transition(State::operational_solo());
                 }
 
@@ -759,7 +1201,7 @@ impl HalpiStateMachine {
                 if now.duration_since(*entry_time)
                     > Duration::from_millis(OFF_STATE_DURATION_MS as u64)
                 {
-                    SCB::sys_reset();
+                    software_reset();
                 } else {
                     Super
                 }
@@ -767,7 +1209,7 @@ impl HalpiStateMachine {
             Event::PowerButtonPress => {
                 // Power button press always triggers restart
                 info!("Power button press detected in powered down blackout state, restarting system");
-                SCB::sys_reset();
+                software_reset();
             }
             _ => Super,
         }
@@ -784,6 +1226,7 @@ impl HalpiStateMachine {
     ///
     /// Restart behavior: Respects auto_restart configuration for timeout-based restart
     /// - Manual shutdowns honor user preference for automatic restart
+    /// - Scheduled shutdowns stay off until the next scheduled "on" slot
     /// - Power button and VIN power changes override auto_restart setting
     /// - If auto_restart is false, system stays off until manual intervention
     ///
@@ -793,7 +1236,8 @@ impl HalpiStateMachine {
     /// - Waiting for restart conditions based on configuration
     ///
     /// Transitions:
-    /// - Auto-restart timeout -> System reset (if auto_restart enabled)
+    /// - Auto-restart timeout -> System reset (if auto_restart enabled and not a scheduled shutdown)
+    /// - ScheduledOn -> System reset (scheduled start)
     /// - PowerButtonPress -> System reset (manual restart, ignores auto_restart)
     /// - VIN power change -> System reset (power cycling recovery, ignores auto_restart)
     #[allow(unused_variables)]
@@ -809,17 +1253,19 @@ impl HalpiStateMachine {
                 if !is_vin_power_available().await {
                     // VIN has been cut - trigger restart for power cycling recovery
                     info!("VIN blackout detected in powered down manual state, restarting system");
-                    SCB::sys_reset();
+                    software_reset();
                 }
 
                 let now = Instant::now();
                 if now.duration_since(*entry_time)
                     > Duration::from_millis(OFF_STATE_DURATION_MS as u64)
                 {
-                    // For command-based shutdowns, respect the auto_restart setting
+                    // For command-based shutdowns, respect the auto_restart setting.
+                    // Scheduled shutdowns wait for the next "on" slot instead.
                     let auto_restart = get_auto_restart().await;
-                    if auto_restart {
-                        SCB::sys_reset();
+                    let scheduled = current_shutdown_reason().await == Some(ShutdownReason::Schedule);
+                    if auto_restart && !scheduled {
+                        software_reset();
                     }
                     // If auto_restart is false, stay in off state indefinitely
                     Super
@@ -830,7 +1276,11 @@ impl HalpiStateMachine {
             Event::PowerButtonPress => {
                 // Power button press always triggers restart, regardless of auto_restart setting
                 info!("Power button press detected in powered down manual state, restarting system");
-                SCB::sys_reset();
+                software_reset();
+            }
+            Event::ScheduledOn => {
+                info!("Scheduled start in powered down manual state, restarting system");
+                software_reset();
             }
             _ => Super,
         }
@@ -878,6 +1328,7 @@ impl HalpiStateMachine {
             }
             Event::WatchdogPing => {
                 context.host_watchdog_last_ping = Instant::now();
+                context.boot_watchdog_timeout_ms = 0;
                 Transition(State::operational_co_op()) // Return to co-op mode
             }
             _ => Super,
@@ -943,13 +1394,19 @@ impl HalpiStateMachine {
     /// - LED shows standby pattern (minimal/dim indication)
     ///
     /// Transitions:
-    /// - ComputeModuleOn -> Operational(solo) (wake from standby, return to normal operation)
+    /// - ComputeModuleOn -> Operational(solo), or Operational(co-op) if the boot watchdog is
+    ///   enabled (wake from standby, return to normal operation)
+    /// - ScheduledOn -> System reset (scheduled start; the reset power-cycles the CM5)
     #[allow(unused_variables)]
     #[state(entry_action = "enter_standby")]
     async fn standby(event: &Event, context: &mut Context) -> Outcome<State> {
         match event {
             // FIXME: Which events should be handled here?
-            Event::ComputeModuleOn => Transition(State::operational_solo()), // Start in solo mode
+            Event::ComputeModuleOn => Transition(booted_state(context).await),
+            Event::ScheduledOn => {
+                info!("Scheduled start in standby, restarting system");
+                software_reset();
+            }
             _ => Super,
         }
     }
@@ -958,6 +1415,38 @@ impl HalpiStateMachine {
     async fn enter_standby(context: &mut Context) {
         context.set_led_pattern(&State::standby()).await;
     }
+
+    /// Factory test mode, entered when the TEST_MODE pin is asserted at boot
+    ///
+    /// Purpose:
+    /// - Suspends all automatic power management
+    /// - Lets the test sequencer drive the power outputs directly
+    ///
+    /// Hardware state:
+    /// - Outputs set by SetTestOutputs events from the test sequencer
+    /// - LED pattern set by the test sequencer
+    ///
+    /// Transitions:
+    /// - None; the system stays in test mode until reset
+    #[allow(unused_variables)]
+    #[state(entry_action = "enter_test_mode")]
+    async fn test_mode(event: &Event, context: &mut Context) -> Outcome<State> {
+        match event {
+            Event::SetTestOutputs(bits) => {
+                context.outputs.set_test_outputs(*bits);
+                Handled
+            }
+            _ => Super,
+        }
+    }
+
+    #[action]
+    async fn enter_test_mode(context: &mut Context) {
+        warn!("Entering factory test mode");
+        context.outputs.power_off();
+        context.set_led_pattern(&State::test_mode()).await;
+        request_test_run();
+    }
 }
 
 #[task]
@@ -981,44 +1470,38 @@ pub async fn state_machine_task(smor: StateMachineOutputResources) {
         Err(_) => error!("Failed to initialize state machine"),
     }
 
-    let mut ticker = Ticker::every(Duration::from_millis(50));
+    let tick_interval = Duration::from_millis(50);
+    let mut next_tick = Instant::now() + tick_interval;
 
     let receiver = STATE_MACHINE_EVENT_CHANNEL.receiver();
 
     info!("State machine task initialized");
 
     let mut prev_cm_on = false;
+    let mut mcu_temp_alarm_active = false;
+    let mut prev_vscap_alarm = false;
 
     loop {
-        // Handle state machine transitions
-        ticker.next().await;
-
         let mut events_to_process = Vec::new();
 
-        while !receiver.is_empty() {
-            // Check for events from the channel
-            let event = receiver.receive().await;
-            match event {
-                StateMachineEvents::Shutdown => {
-                    events_to_process.push(Event::Shutdown);
-                }
-                StateMachineEvents::SetHostWatchdogTimeout(timeout) => {
-                    events_to_process.push(Event::SetWatchdogTimeout(timeout));
-                }
-                StateMachineEvents::HostWatchdogPing => {
-                    events_to_process.push(Event::WatchdogPing);
-                }
-                StateMachineEvents::StandbyShutdown => {
-                    events_to_process.push(Event::StandbyShutdown);
-                }
-                StateMachineEvents::Off => {
-                    events_to_process.push(Event::Off);
-                }
-                StateMachineEvents::PowerButtonPress => {
-                    events_to_process.push(Event::PowerButtonPress);
-                }
+        // Wait for the next tick, but handle external events as soon as they arrive
+        if let Ok(event) = with_deadline(next_tick, receiver.receive()).await {
+            events_to_process.push(to_internal_event(event));
+            while let Ok(event) = receiver.try_receive() {
+                events_to_process.push(to_internal_event(event));
+            }
+            for event in events_to_process.drain(..) {
+                state_machine
+                    .handle_with_context(&event, &mut context)
+                    .await;
+                record_state_machine_state(state_machine.state(), &event, &context).await;
+            }
+            // A steady stream of external events must not postpone an overdue tick
+            if Instant::now() < next_tick {
+                continue;
             }
         }
+        next_tick += tick_interval;
 
         // Generate events based on current inputs (edge detection)
         let inputs = INPUTS.lock().await;
@@ -1034,13 +1517,59 @@ pub async fn state_machine_task(smor: StateMachineOutputResources) {
             prev_cm_on = cm_on;
         }
 
+        // MCU temperature alarm, logged once per excursion
+        let mcu_temp = inputs.mcu_temp;
+        if !mcu_temp_alarm_active && mcu_temp > MCU_TEMP_ALARM {
+            warn!("MCU temperature alarm: {} K", mcu_temp);
+            log_event(LogEventKind::ThermalAlarm, ((mcu_temp - 273.15) * 100.0) as u32);
+            mcu_temp_alarm_active = true;
+        } else if mcu_temp_alarm_active && mcu_temp < MCU_TEMP_ALARM - MCU_TEMP_ALARM_HYSTERESIS {
+            mcu_temp_alarm_active = false;
+        }
+
         drop(inputs);
 
         // Vscap alarm detection
-        let (_, vscap_alarm) = get_vscap_status().await;
+        let (vscap, vscap_alarm) = get_vscap_status().await;
         if vscap_alarm && !context.vscap_alarm_active {
             events_to_process.push(Event::SupercapOvervoltage);
         }
+        // Report each excursion once, also in states that do not latch the alarm
+        if vscap_alarm && !prev_vscap_alarm {
+            log_event(LogEventKind::SupercapAlarm, (vscap * 1000.0) as u32);
+            post_host_event_with_payload(HostEvent::SupercapAlarm, (vscap * 1000.0) as u32).await;
+        }
+        prev_vscap_alarm = vscap_alarm;
+
+        // Weekly schedule
+        match poll_schedule().await {
+            Some(ScheduleAction::On) => events_to_process.push(Event::ScheduledOn),
+            Some(ScheduleAction::Off) => events_to_process.push(Event::ScheduledOff),
+            None => {}
+        }
+
+        // Show CM5 boot error codes until the next state change, only while the CM5 boots
+        let boot_code = if matches!(
+            state_machine.state(),
+            State::SystemStartup {} | State::OperationalSolo {} | State::OperationalCoOp {}
+        ) {
+            take_boot_code().await
+        } else {
+            None
+        };
+        if let Some(code) = boot_code {
+            context.set_boot_code_led_pattern(code).await;
+        }
+
+        // CM5 liveness, only checked in solo mode
+        let liveness = if matches!(state_machine.state(), State::OperationalSolo {}) {
+            poll_liveness().await
+        } else {
+            None
+        };
+        if let Some(recovery) = liveness {
+            events_to_process.push(Event::LivenessLost(recovery));
+        }
 
         // Add a regular tick event
         events_to_process.push(Event::Tick);
@@ -1052,7 +1581,7 @@ pub async fn state_machine_task(smor: StateMachineOutputResources) {
                 .handle_with_context(&event, &mut context)
                 .await;
             // Record the current state
-            record_state_machine_state(state_machine.state()).await;
+            record_state_machine_state(state_machine.state(), &event, &context).await;
         }
     }
 }
@@ -0,0 +1,262 @@
+//! Long-term telemetry logger.
+//!
+//! Records VIN, VSCAP, IIN and the MCU temperature at a configurable interval
+//! (seconds, stored in the config) into a dedicated flash partition (TELEMETRY in
+//! memory.x), whatever the state of the CM5. The partition is a ring of
+//! fixed-size slots: record `seq` lives in slot `seq % capacity`, so the host can
+//! download any part of the history in chunks over I2C (register 0x94) without
+//! scanning the log. A page is erased right before its first slot is written,
+//! which drops the oldest page of records once the ring is full.
+
+use defmt::{debug, error, info};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::config::*;
+use crate::flash_layout::{PAGE_SIZE, get_telemetry_range};
+use crate::rtc::rtc_epoch_s;
+use crate::tasks::config_manager::{get_telemetry_log_interval, wait_for_config_loaded};
+use crate::tasks::gpio_input::INPUTS;
+use crate::{MFlashType, OM_FLASH};
+
+/// Size of a record in flash and in the I2C wire format
+const RECORD_SIZE: usize = 16;
+const SLOTS_PER_PAGE: u32 = PAGE_SIZE / RECORD_SIZE as u32;
+/// Sequence number of an erased slot
+const ERASED_SEQ: u32 = 0xffff_ffff;
+/// Set in the stored sequence number when the timestamp is UTC
+const UTC_FLAG: u32 = 0x8000_0000;
+
+/// Size of a download chunk: record count, then the records
+pub const TELEMETRY_LOG_CHUNK_SIZE: usize = 1 + TELEMETRY_LOG_CHUNK_RECORDS * RECORD_SIZE;
+
+#[derive(Clone, Copy, defmt::Format)]
+struct TelemetryRecord {
+    seq: u32,
+    /// UTC seconds since the Unix epoch if `utc` is set, otherwise uptime in seconds
+    timestamp: u32,
+    utc: bool,
+    /// Scaled as in registers 0x20, 0x21, 0x22 and 0x23
+    vin: u16,
+    vscap: u16,
+    iin: u16,
+    mcu_temp: u16,
+}
+
+impl TelemetryRecord {
+    /// Stored format: sequence number (u32 BE, top bit set if the timestamp is UTC),
+    /// timestamp (u32 BE), then VIN, VSCAP, IIN and MCU temperature (u16 BE each)
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0u8; RECORD_SIZE];
+        let seq = if self.utc { self.seq | UTC_FLAG } else { self.seq };
+        bytes[0..4].copy_from_slice(&seq.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.vin.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.vscap.to_be_bytes());
+        bytes[12..14].copy_from_slice(&self.iin.to_be_bytes());
+        bytes[14..16].copy_from_slice(&self.mcu_temp.to_be_bytes());
+        bytes
+    }
+}
+
+struct LogState {
+    /// Sequence number of the next record to be written
+    next_seq: u32,
+    /// Set once the head of the ring has been located at boot
+    ready: bool,
+}
+
+static TELEMETRY_LOG: Mutex<CriticalSectionRawMutex, LogState> = Mutex::new(LogState {
+    next_seq: 0,
+    ready: false,
+});
+
+/// Signalled to erase the whole log
+static ERASE_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+fn capacity() -> u32 {
+    let range = get_telemetry_range();
+    (range.end - range.start) / RECORD_SIZE as u32
+}
+
+fn slot_offset(seq: u32) -> u32 {
+    get_telemetry_range().start + (seq % capacity()) * RECORD_SIZE as u32
+}
+
+/// Oldest sequence number still in flash, given the next one to be written
+fn oldest_seq(next_seq: u32) -> u32 {
+    if next_seq == 0 {
+        return 0;
+    }
+    // Writing the first slot of the head page erased a whole page of the oldest records
+    let head_page_start = (next_seq - 1) - (next_seq - 1) % SLOTS_PER_PAGE;
+    (head_page_start + SLOTS_PER_PAGE).saturating_sub(capacity())
+}
+
+/// Sequence number without the UTC flag, or ERASED_SEQ for an erased slot
+fn stored_seq(bytes: [u8; 4]) -> u32 {
+    match u32::from_be_bytes(bytes) {
+        ERASED_SEQ => ERASED_SEQ,
+        seq => seq & !UTC_FLAG,
+    }
+}
+
+async fn read_seq(flash: &mut crate::FlashType<'_>, offset: u32) -> u32 {
+    let mut bytes = [0u8; 4];
+    if let Err(e) = flash.read(offset, &mut bytes).await {
+        error!("Failed to read telemetry log: {}", defmt::Debug2Format(&e));
+        return ERASED_SEQ;
+    }
+    stored_seq(bytes)
+}
+
+/// Find the sequence number of the next record: the first slot of each page holds the
+/// lowest sequence number of that page, so the head is in the page with the highest one.
+async fn find_next_seq(flash: &mut crate::FlashType<'_>) -> u32 {
+    let range = get_telemetry_range();
+    let mut head_page = None;
+    let mut head_seq = 0;
+    for page_start in (range.start..range.end).step_by(PAGE_SIZE as usize) {
+        let seq = read_seq(flash, page_start).await;
+        if seq != ERASED_SEQ && (head_page.is_none() || seq > head_seq) {
+            head_page = Some(page_start);
+            head_seq = seq;
+        }
+    }
+    let Some(page_start) = head_page else {
+        return 0;
+    };
+    let mut next_seq = head_seq + 1;
+    for slot in 1..SLOTS_PER_PAGE {
+        let seq = read_seq(flash, page_start + slot * RECORD_SIZE as u32).await;
+        if seq != head_seq + slot {
+            break;
+        }
+        next_seq = seq + 1;
+    }
+    next_seq
+}
+
+/// Erase the whole log
+pub fn erase_telemetry_log() {
+    ERASE_SIGNAL.signal(());
+}
+
+/// Log status in I2C wire format: interval in seconds (u16 BE), oldest sequence number
+/// (u32 BE) and next sequence number (u32 BE)
+pub async fn get_telemetry_log_status() -> [u8; 10] {
+    let next_seq = TELEMETRY_LOG.lock().await.next_seq;
+    let mut bytes = [0u8; 10];
+    bytes[0..2].copy_from_slice(&get_telemetry_log_interval().await.to_be_bytes());
+    bytes[2..6].copy_from_slice(&oldest_seq(next_seq).to_be_bytes());
+    bytes[6..10].copy_from_slice(&next_seq.to_be_bytes());
+    bytes
+}
+
+/// Read a chunk of records starting at sequence number `start` in I2C wire format: number
+/// of records, then the records. Records older than the oldest one are skipped; the chunk
+/// is empty once `start` reaches the next sequence number.
+pub async fn read_telemetry_log_chunk(start: u32) -> [u8; TELEMETRY_LOG_CHUNK_SIZE] {
+    let mut response = [0u8; TELEMETRY_LOG_CHUNK_SIZE];
+    let (ready, next_seq) = {
+        let state = TELEMETRY_LOG.lock().await;
+        (state.ready, state.next_seq)
+    };
+    if !ready {
+        return response;
+    }
+
+    let mut flash = OM_FLASH.get().await.lock().await;
+    let mut count = 0;
+    let mut seq = start.max(oldest_seq(next_seq));
+    while seq < next_seq && count < TELEMETRY_LOG_CHUNK_RECORDS {
+        let offset = 1 + count * RECORD_SIZE;
+        let record = &mut response[offset..offset + RECORD_SIZE];
+        if let Err(e) = flash.read(slot_offset(seq), record).await {
+            error!("Failed to read telemetry log: {}", defmt::Debug2Format(&e));
+            break;
+        }
+        // Skip slots that do not hold the expected record, e.g. after an interrupted write
+        if stored_seq([record[0], record[1], record[2], record[3]]) == seq {
+            count += 1;
+        } else {
+            record.fill(0);
+        }
+        seq += 1;
+    }
+    response[0] = count as u8;
+    response
+}
+
+async fn sample() -> TelemetryRecord {
+    let inputs = INPUTS.lock().await;
+    let temperature_range = MAX_TEMPERATURE_VALUE - MIN_TEMPERATURE_VALUE;
+    let epoch_s = rtc_epoch_s();
+    TelemetryRecord {
+        seq: 0,
+        timestamp: epoch_s.unwrap_or_else(|| Instant::now().as_secs() as u32),
+        utc: epoch_s.is_some(),
+        vin: (65535.0 * inputs.vin / VIN_MAX_VALUE) as u16,
+        vscap: (65535.0 * inputs.vscap / VSCAP_MAX_VALUE) as u16,
+        iin: (65535.0 * inputs.iin / IIN_MAX_VALUE) as u16,
+        mcu_temp: (65535.0 * (inputs.mcu_temp - MIN_TEMPERATURE_VALUE) / temperature_range) as u16,
+    }
+}
+
+#[task]
+pub async fn telemetry_log_task(flash: &'static MFlashType<'static>) {
+    info!("Starting telemetry log task");
+
+    let next_seq = find_next_seq(&mut *flash.lock().await).await;
+    {
+        let mut state = TELEMETRY_LOG.lock().await;
+        state.next_seq = next_seq;
+        state.ready = true;
+    }
+    info!("Telemetry log: next record {}", next_seq);
+
+    // Let the analog inputs settle before the first record
+    wait_for_config_loaded().await;
+    Timer::after(Duration::from_millis(TELEMETRY_WARMUP_MS as u64)).await;
+
+    loop {
+        let mut record = sample().await;
+        let seq = TELEMETRY_LOG.lock().await.next_seq;
+        record.seq = seq;
+
+        {
+            let mut flash = flash.lock().await;
+            let offset = slot_offset(seq);
+            if seq % SLOTS_PER_PAGE == 0 {
+                if let Err(e) = flash.erase(offset, offset + PAGE_SIZE).await {
+                    error!("Failed to erase telemetry log page: {}", defmt::Debug2Format(&e));
+                }
+            }
+            debug!("Logging telemetry {}", record);
+            match flash.write(offset, &record.to_bytes()).await {
+                Ok(()) => TELEMETRY_LOG.lock().await.next_seq = seq.wrapping_add(1),
+                Err(e) => error!("Failed to write telemetry log: {}", defmt::Debug2Format(&e)),
+            }
+        }
+
+        let interval = get_telemetry_log_interval().await.max(MIN_TELEMETRY_LOG_INTERVAL_S);
+        let interval = Duration::from_secs(interval as u64);
+        if with_timeout(interval, ERASE_SIGNAL.wait()).await.is_ok() {
+            info!("Erasing telemetry log");
+            let range = get_telemetry_range();
+            for page_start in (range.start..range.end).step_by(PAGE_SIZE as usize) {
+                let result = flash.lock().await.erase(page_start, page_start + PAGE_SIZE).await;
+                if let Err(e) = result {
+                    error!("Failed to erase telemetry log: {}", defmt::Debug2Format(&e));
+                }
+                // Erase page by page so that other tasks get the flash in between
+                Timer::after(Duration::from_millis(1)).await;
+            }
+            TELEMETRY_LOG.lock().await.next_seq = 0;
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Rolling telemetry statistics.
+//!
+//! Samples VIN, VSCAP, IIN and both temperatures at the analog publishing rate
+//! and keeps min/max/mean over a rolling 1-minute window, a rolling 1-hour
+//! window and since boot (or since the last reset command). Short dips that
+//! fall between host polls of registers 0x20-0x24 still show up in the minima.
+//! The rolling windows are built from fixed-length buckets, so they advance in
+//! bucket-sized steps.
+
+use defmt::info;
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, Instant, Ticker, Timer};
+
+use crate::config::*;
+use crate::tasks::config_manager::wait_for_config_loaded;
+use crate::tasks::gpio_input::INPUTS;
+
+/// Channels: VIN, VSCAP, IIN, MCU temperature, PCB temperature
+const NUM_CHANNELS: usize = 5;
+/// Windows: 1 minute, 1 hour, since boot
+const NUM_WINDOWS: usize = 3;
+
+/// Size of the statistics in the I2C wire format: min, max and mean (u16 each)
+/// per channel per window
+pub const TELEMETRY_STATS_SIZE: usize = NUM_WINDOWS * NUM_CHANNELS * 3 * 2;
+
+#[derive(Clone, Copy)]
+struct Accumulator {
+    min: f32,
+    max: f32,
+    sum: f64,
+    count: u64,
+}
+
+impl Accumulator {
+    const EMPTY: Self = Self {
+        min: f32::MAX,
+        max: f32::MIN,
+        sum: 0.0,
+        count: 0,
+    };
+
+    fn add(&mut self, value: f32) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as f64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// Min, max and mean, or zeros if no samples have been added
+    fn summary(&self) -> [f32; 3] {
+        if self.count == 0 {
+            return [0.0; 3];
+        }
+        [self.min, self.max, (self.sum / self.count as f64) as f32]
+    }
+}
+
+type ChannelAccumulators = [Accumulator; NUM_CHANNELS];
+
+const EMPTY_CHANNELS: ChannelAccumulators = [Accumulator::EMPTY; NUM_CHANNELS];
+
+/// Rolling window of N buckets of `bucket_ms` each
+struct RollingWindow<const N: usize> {
+    buckets: [ChannelAccumulators; N],
+    current: usize,
+    bucket_start: Instant,
+    bucket_ms: u64,
+}
+
+impl<const N: usize> RollingWindow<N> {
+    const fn new(bucket_ms: u64) -> Self {
+        Self {
+            buckets: [EMPTY_CHANNELS; N],
+            current: 0,
+            bucket_start: Instant::from_ticks(0),
+            bucket_ms,
+        }
+    }
+
+    fn reset(&mut self, now: Instant) {
+        self.buckets = [EMPTY_CHANNELS; N];
+        self.current = 0;
+        self.bucket_start = now;
+    }
+
+    fn add(&mut self, now: Instant, values: &[f32; NUM_CHANNELS]) {
+        // Advance past expired buckets, clearing them
+        let elapsed = now.duration_since(self.bucket_start).as_millis() / self.bucket_ms;
+        for _ in 0..elapsed.min(N as u64) {
+            self.current = (self.current + 1) % N;
+            self.buckets[self.current] = EMPTY_CHANNELS;
+        }
+        if elapsed > 0 {
+            self.bucket_start += Duration::from_millis(elapsed * self.bucket_ms);
+        }
+        for (accumulator, value) in self.buckets[self.current].iter_mut().zip(values) {
+            accumulator.add(*value);
+        }
+    }
+
+    fn summary(&self) -> ChannelAccumulators {
+        let mut total = EMPTY_CHANNELS;
+        for bucket in &self.buckets {
+            for (total, accumulator) in total.iter_mut().zip(bucket) {
+                total.merge(accumulator);
+            }
+        }
+        total
+    }
+}
+
+struct TelemetryStats {
+    minute: RollingWindow<TELEMETRY_MINUTE_BUCKETS>,
+    hour: RollingWindow<TELEMETRY_HOUR_BUCKETS>,
+    since_boot: ChannelAccumulators,
+}
+
+static TELEMETRY_STATS: Mutex<CriticalSectionRawMutex, TelemetryStats> =
+    Mutex::new(TelemetryStats {
+        minute: RollingWindow::new(60_000 / TELEMETRY_MINUTE_BUCKETS as u64),
+        hour: RollingWindow::new(3_600_000 / TELEMETRY_HOUR_BUCKETS as u64),
+        since_boot: EMPTY_CHANNELS,
+    });
+
+/// Clear all windows
+pub async fn reset_telemetry_stats() {
+    let now = Instant::now();
+    let mut stats = TELEMETRY_STATS.lock().await;
+    stats.minute.reset(now);
+    stats.hour.reset(now);
+    stats.since_boot = EMPTY_CHANNELS;
+}
+
+/// Scale a value to u16 using the same scaling as registers 0x20-0x24
+fn scale_to_u16(channel: usize, value: f32) -> u16 {
+    let temperature_range = MAX_TEMPERATURE_VALUE - MIN_TEMPERATURE_VALUE;
+    let scaled = match channel {
+        0 => 65535.0 * value / VIN_MAX_VALUE,
+        1 => 65535.0 * value / VSCAP_MAX_VALUE,
+        2 => 65535.0 * value / IIN_MAX_VALUE,
+        _ => 65535.0 * (value - MIN_TEMPERATURE_VALUE) / temperature_range,
+    };
+    scaled as u16
+}
+
+/// Statistics in I2C wire format: for each window (1 minute, 1 hour, since boot) and
+/// each channel (VIN, VSCAP, IIN, MCU temperature, PCB temperature) the min, max and
+/// mean as scaled u16 big-endian values. Channels without samples read as zero.
+pub async fn get_telemetry_stats() -> [u8; TELEMETRY_STATS_SIZE] {
+    let windows = {
+        let stats = TELEMETRY_STATS.lock().await;
+        [stats.minute.summary(), stats.hour.summary(), stats.since_boot]
+    };
+    let mut bytes = [0u8; TELEMETRY_STATS_SIZE];
+    let mut offset = 0;
+    for window in &windows {
+        for (channel, accumulator) in window.iter().enumerate() {
+            for value in accumulator.summary() {
+                let scaled = if accumulator.count == 0 {
+                    0
+                } else {
+                    scale_to_u16(channel, value)
+                };
+                bytes[offset..offset + 2].copy_from_slice(&scaled.to_be_bytes());
+                offset += 2;
+            }
+        }
+    }
+    bytes
+}
+
+#[task]
+pub async fn telemetry_stats_task() {
+    info!("Starting telemetry statistics task");
+
+    // Let the analog filters settle so that start-up values do not end up in the minima
+    wait_for_config_loaded().await;
+    Timer::after(Duration::from_millis(TELEMETRY_WARMUP_MS as u64)).await;
+    reset_telemetry_stats().await;
+    let mut ticker =
+        Ticker::every(Duration::from_millis(TELEMETRY_SAMPLE_INTERVAL_MS as u64));
+
+    loop {
+        ticker.next().await;
+
+        let values = {
+            let inputs = INPUTS.lock().await;
+            [inputs.vin, inputs.vscap, inputs.iin, inputs.mcu_temp, inputs.pcb_temp]
+        };
+        let now = Instant::now();
+        let mut stats = TELEMETRY_STATS.lock().await;
+        stats.minute.add(now, &values);
+        stats.hour.add(now, &values);
+        for (accumulator, value) in stats.since_boot.iter_mut().zip(&values) {
+            accumulator.add(*value);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Factory test mode sequencer.
+//!
+//! Test mode is entered when the TEST_MODE pin is asserted at boot. The state
+//! machine then moves to `TestMode`, suspends all automatic power management and
+//! hands control of its outputs to this task. The sequencer steps through the
+//! LED colours, toggles every output, checks the analog and temperature readings
+//! and waits for the operator to press both buttons.
+//!
+//! Outputs stay owned by their regular tasks: power rails are driven through the
+//! state machine, USB disables through the config manager and PWR_BTN_OUT through
+//! the power button task. Checks for outputs without a feedback signal report
+//! `Unverified` once the toggle sequence ran; the test fixture verifies them
+//! externally, and they do not count towards the overall result.
+//!
+//! Results are reported over I2C register 0x70.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use defmt::{info, warn};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use smart_leds::RGB8;
+use smart_leds::colors::*;
+
+use crate::config::*;
+use crate::led_patterns::{get_test_color_pattern, get_test_result_pattern};
+use crate::tasks::config_manager::{get_usb_port_state, set_usb_port_state};
+use crate::tasks::gpio_input::{INPUTS, Inputs};
+use crate::tasks::led_blinker::{LED_BLINKER_EVENT_CHANNEL, LEDBlinkerEvents};
+use crate::tasks::power_button::{POWER_BUTTON_EVENT_CHANNEL, PowerButtonEvents};
+use crate::tasks::state_machine::{STATE_MACHINE_EVENT_CHANNEL, StateMachineEvents};
+
+/// Output bits used with `StateMachineEvents::SetTestOutputs`.
+/// A set bit means the output is asserted (rail enabled, PCIe put to sleep).
+pub const TEST_OUTPUT_EN_5V: u8 = 0x01;
+pub const TEST_OUTPUT_EN_3V3: u8 = 0x02;
+pub const TEST_OUTPUT_PCIE_SLEEP: u8 = 0x04;
+
+/// Individual checks performed by the test sequence. The discriminant is the
+/// index of the result byte in the I2C report and is part of the I2C API.
+#[derive(Clone, Copy, defmt::Format)]
+#[repr(u8)]
+pub enum TestCheck {
+    Leds = 0,
+    UsbPorts = 1,
+    En5v = 2,
+    En3v3 = 3,
+    PcieSleep = 4,
+    PwrBtnOut = 5,
+    Vin = 6,
+    Vscap = 7,
+    Iin = 8,
+    McuTemp = 9,
+    PcbTemp = 10,
+    PowerButton = 11,
+    UserButton = 12,
+}
+
+pub const NUM_TEST_CHECKS: usize = 13;
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum TestResult {
+    NotRun = 0,
+    Running = 1,
+    Pass = 2,
+    Fail = 3,
+    /// The check ran but has no feedback signal; the test fixture verifies it
+    Unverified = 4,
+}
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+pub enum TestStatus {
+    /// Test mode is not active
+    Inactive = 0,
+    /// Test sequence is in progress
+    Running = 1,
+    /// Test sequence completed and all verified checks passed
+    Passed = 2,
+    /// Test sequence completed and at least one check failed
+    Failed = 3,
+}
+
+struct TestReport {
+    status: TestStatus,
+    results: [TestResult; NUM_TEST_CHECKS],
+}
+
+impl TestReport {
+    const fn new() -> Self {
+        Self {
+            status: TestStatus::Inactive,
+            results: [TestResult::NotRun; NUM_TEST_CHECKS],
+        }
+    }
+}
+
+static TEST_REPORT: Mutex<CriticalSectionRawMutex, TestReport> = Mutex::new(TestReport::new());
+
+/// Set when the TEST_MODE pin was asserted at boot
+static TEST_MODE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Signalled to start a test sequence run
+static TEST_RUN_SIGNAL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+pub fn set_test_mode_requested() {
+    TEST_MODE_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_test_mode_requested() -> bool {
+    TEST_MODE_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Request a run of the test sequence. Ignored unless test mode was entered at boot.
+pub fn request_test_run() {
+    TEST_RUN_SIGNAL.signal(());
+}
+
+/// Get the test report in I2C wire format: status byte followed by one result byte per check
+pub async fn get_test_report() -> [u8; 1 + NUM_TEST_CHECKS] {
+    let report = TEST_REPORT.lock().await;
+    let mut bytes = [0u8; 1 + NUM_TEST_CHECKS];
+    bytes[0] = report.status as u8;
+    for (i, result) in report.results.iter().enumerate() {
+        bytes[1 + i] = *result as u8;
+    }
+    bytes
+}
+
+async fn set_result(check: TestCheck, result: TestResult) {
+    info!("Test check {:?}: {:?}", check, result);
+    TEST_REPORT.lock().await.results[check as usize] = result;
+}
+
+async fn set_leds(color: RGB8) {
+    LED_BLINKER_EVENT_CHANNEL
+        .send(LEDBlinkerEvents::SetPattern(get_test_color_pattern(color)))
+        .await;
+}
+
+async fn set_outputs(bits: u8) {
+    STATE_MACHINE_EVENT_CHANNEL
+        .send(StateMachineEvents::SetTestOutputs(bits))
+        .await;
+}
+
+async fn read_inputs() -> Inputs {
+    INPUTS.lock().await.clone()
+}
+
+/// Step through the primary colours. There is no feedback from the LEDs, so
+/// the operator verifies the colours visually.
+async fn test_leds() -> TestResult {
+    for color in [RED, GREEN, BLUE, WHITE] {
+        set_leds(color).await;
+        Timer::after(Duration::from_millis(TEST_MODE_STEP_DURATION_MS as u64)).await;
+    }
+    TestResult::Unverified
+}
+
+/// Enable and disable each USB port in turn. The read-back port state only reflects
+/// the requested state, so a port is never verified beyond that.
+async fn test_usb_ports() -> TestResult {
+    let mut result = TestResult::Unverified;
+    for port in 0..4u8 {
+        let bits = 1 << port;
+        set_usb_port_state(bits).await;
+        Timer::after(Duration::from_millis(TEST_MODE_SETTLE_MS as u64)).await;
+        if get_usb_port_state().await != bits {
+            warn!("USB port {} did not enable", port);
+            result = TestResult::Fail;
+        }
+    }
+    set_usb_port_state(0x00).await;
+    Timer::after(Duration::from_millis(TEST_MODE_SETTLE_MS as u64)).await;
+    if get_usb_port_state().await != 0x00 {
+        result = TestResult::Fail;
+    }
+    result
+}
+
+/// Toggle a state machine output on and off. Returns the input snapshots taken
+/// while the output was asserted and after it was released.
+async fn toggle_output(bit: u8) -> (Inputs, Inputs) {
+    set_outputs(bit).await;
+    Timer::after(Duration::from_millis(TEST_MODE_STEP_DURATION_MS as u64)).await;
+    let on = read_inputs().await;
+    set_outputs(0).await;
+    Timer::after(Duration::from_millis(TEST_MODE_STEP_DURATION_MS as u64)).await;
+    let off = read_inputs().await;
+    (on, off)
+}
+
+async fn test_pwr_btn_out() -> TestResult {
+    POWER_BUTTON_EVENT_CHANNEL.send(PowerButtonEvents::Press).await;
+    Timer::after(Duration::from_millis(TEST_MODE_STEP_DURATION_MS as u64)).await;
+    POWER_BUTTON_EVENT_CHANNEL.send(PowerButtonEvents::Release).await;
+    Timer::after(Duration::from_millis(TEST_MODE_SETTLE_MS as u64)).await;
+    TestResult::Unverified
+}
+
+fn range_result(value: f32, min: f32, max: f32) -> TestResult {
+    if value >= min && value <= max {
+        TestResult::Pass
+    } else {
+        TestResult::Fail
+    }
+}
+
+/// Wait for the operator to press a button. Buttons are active low.
+async fn wait_for_button(color: RGB8, pressed: fn(&Inputs) -> bool) -> TestResult {
+    set_leds(color).await;
+    let deadline = Instant::now() + Duration::from_millis(TEST_MODE_BUTTON_TIMEOUT_MS as u64);
+    while Instant::now() < deadline {
+        if pressed(&*INPUTS.lock().await) {
+            return TestResult::Pass;
+        }
+        Timer::after(Duration::from_millis(10)).await;
+    }
+    TestResult::Fail
+}
+
+async fn run_test_sequence() {
+    {
+        let mut report = TEST_REPORT.lock().await;
+        report.status = TestStatus::Running;
+        report.results = [TestResult::Running; NUM_TEST_CHECKS];
+    }
+    info!("Starting factory test sequence");
+
+    let result = test_leds().await;
+    set_result(TestCheck::Leds, result).await;
+
+    let result = test_usb_ports().await;
+    set_result(TestCheck::UsbPorts, result).await;
+
+    // EN_5V is verified through the PG_5V power good feedback
+    let (on, off) = toggle_output(TEST_OUTPUT_EN_5V).await;
+    let result = if on.pg_5v && !off.pg_5v {
+        TestResult::Pass
+    } else {
+        TestResult::Fail
+    };
+    set_result(TestCheck::En5v, result).await;
+
+    toggle_output(TEST_OUTPUT_EN_3V3).await;
+    set_result(TestCheck::En3v3, TestResult::Unverified).await;
+
+    toggle_output(TEST_OUTPUT_PCIE_SLEEP).await;
+    set_result(TestCheck::PcieSleep, TestResult::Unverified).await;
+
+    let result = test_pwr_btn_out().await;
+    set_result(TestCheck::PwrBtnOut, result).await;
+
+    let inputs = read_inputs().await;
+    set_result(
+        TestCheck::Vin,
+        range_result(inputs.vin, DEFAULT_VIN_POWER_THRESHOLD, VIN_MAX_VALUE),
+    )
+    .await;
+    set_result(
+        TestCheck::Vscap,
+        range_result(inputs.vscap, TEST_MODE_VSCAP_MIN, VSCAP_MAX_ALARM),
+    )
+    .await;
+    set_result(
+        TestCheck::Iin,
+        range_result(inputs.iin, TEST_MODE_IIN_MIN, IIN_MAX_VALUE),
+    )
+    .await;
+    set_result(
+        TestCheck::McuTemp,
+        range_result(inputs.mcu_temp, MIN_TEMPERATURE_VALUE, MAX_TEMPERATURE_VALUE),
+    )
+    .await;
+    // pcb_temp stays at zero if the TMP112 never answered
+    set_result(
+        TestCheck::PcbTemp,
+        range_result(inputs.pcb_temp, MIN_TEMPERATURE_VALUE, MAX_TEMPERATURE_VALUE),
+    )
+    .await;
+
+    let result = wait_for_button(BLUE, |inputs| !inputs.pwr_btn).await;
+    set_result(TestCheck::PowerButton, result).await;
+
+    let result = wait_for_button(MAGENTA, |inputs| !inputs.user_btn).await;
+    set_result(TestCheck::UserButton, result).await;
+
+    // Leave the board in a safe state: rails off, USB ports enabled
+    set_outputs(0).await;
+    set_usb_port_state(0x0f).await;
+
+    let passed = {
+        let mut report = TEST_REPORT.lock().await;
+        let passed = report
+            .results
+            .iter()
+            .all(|r| matches!(r, TestResult::Pass | TestResult::Unverified));
+        report.status = if passed {
+            TestStatus::Passed
+        } else {
+            TestStatus::Failed
+        };
+        passed
+    };
+    info!("Factory test sequence complete, passed: {}", passed);
+
+    LED_BLINKER_EVENT_CHANNEL
+        .send(LEDBlinkerEvents::SetPattern(get_test_result_pattern(passed)))
+        .await;
+}
+
+#[task]
+pub async fn test_mode_task() {
+    info!("Test mode task initialized");
+
+    loop {
+        TEST_RUN_SIGNAL.wait().await;
+
+        if !is_test_mode_requested() {
+            warn!("Test run requested while not in test mode");
+            continue;
+        }
+
+        run_test_sequence().await;
+    }
+}
@@ -0,0 +1,93 @@
+//! RAM ring buffer of recent state machine transitions.
+//!
+//! Each transition is stored with the source and target state, the event that
+//! triggered it, the uptime and the VIN and VSCAP voltages at that moment. The
+//! history is read over I2C (register 0x86) one page at a time, newest first,
+//! so that the sequence leading up to an unexpected shutdown can be
+//! reconstructed. The history is lost on reset.
+
+use alloc::collections::VecDeque;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::config::TRANSITION_HISTORY_DEPTH;
+
+/// Transition records returned per I2C page
+pub const TRANSITION_HISTORY_PAGE_RECORDS: usize = 4;
+/// Size of a transition record in the I2C wire format
+const TRANSITION_RECORD_SIZE: usize = 11;
+/// Size of a history page in the I2C wire format: record count followed by the records
+pub const TRANSITION_HISTORY_PAGE_SIZE: usize =
+    1 + TRANSITION_HISTORY_PAGE_RECORDS * TRANSITION_RECORD_SIZE;
+
+#[derive(Clone, Copy, defmt::Format)]
+pub struct TransitionRecord {
+    /// Uptime in milliseconds
+    pub timestamp: u32,
+    /// Source state number (see `state_as_u8`)
+    pub from: u8,
+    /// Target state number
+    pub to: u8,
+    /// Triggering event number (see `event_as_u8`)
+    pub event: u8,
+    /// VIN voltage in mV
+    pub vin_mv: u16,
+    /// VSCAP voltage in mV
+    pub vscap_mv: u16,
+}
+
+impl TransitionRecord {
+    pub fn new(from: u8, to: u8, event: u8, vin: f32, vscap: f32) -> Self {
+        Self {
+            timestamp: Instant::now().as_millis() as u32,
+            from,
+            to,
+            event,
+            vin_mv: (vin * 1000.0) as u16,
+            vscap_mv: (vscap * 1000.0) as u16,
+        }
+    }
+
+    /// I2C wire format: uptime ms (u32 BE), from, to, event, VIN mV (u16 BE), VSCAP mV (u16 BE)
+    fn to_bytes(self) -> [u8; TRANSITION_RECORD_SIZE] {
+        let mut bytes = [0u8; TRANSITION_RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&self.timestamp.to_be_bytes());
+        bytes[4] = self.from;
+        bytes[5] = self.to;
+        bytes[6] = self.event;
+        bytes[7..9].copy_from_slice(&self.vin_mv.to_be_bytes());
+        bytes[9..11].copy_from_slice(&self.vscap_mv.to_be_bytes());
+        bytes
+    }
+}
+
+static TRANSITION_HISTORY: Mutex<CriticalSectionRawMutex, VecDeque<TransitionRecord>> =
+    Mutex::new(VecDeque::new());
+
+/// Append a transition, discarding the oldest one if the history is full
+pub async fn record_transition(record: TransitionRecord) {
+    let mut history = TRANSITION_HISTORY.lock().await;
+    if history.len() >= TRANSITION_HISTORY_DEPTH {
+        history.pop_front();
+    }
+    history.push_back(record);
+}
+
+/// History page in I2C wire format. Page 0 holds the newest transitions. The first
+/// byte is the total number of recorded transitions; unused record slots are zero.
+pub async fn get_transition_history_page(page: u8) -> [u8; TRANSITION_HISTORY_PAGE_SIZE] {
+    let history = TRANSITION_HISTORY.lock().await;
+    let mut bytes = [0u8; TRANSITION_HISTORY_PAGE_SIZE];
+    bytes[0] = history.len() as u8;
+    let records = history
+        .iter()
+        .rev()
+        .skip(page as usize * TRANSITION_HISTORY_PAGE_RECORDS)
+        .take(TRANSITION_HISTORY_PAGE_RECORDS);
+    for (i, record) in records.enumerate() {
+        let offset = 1 + i * TRANSITION_RECORD_SIZE;
+        bytes[offset..offset + TRANSITION_RECORD_SIZE].copy_from_slice(&record.to_bytes());
+    }
+    bytes
+}
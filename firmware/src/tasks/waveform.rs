@@ -0,0 +1,141 @@
+//! Power-loss waveform capture.
+//!
+//! The analog input task feeds VIN, VSCAP and IIN into a ring buffer at a high
+//! rate, straight from the ADC bursts and before the publishing filters. When
+//! the state machine goes from an operational state to a blackout state, the
+//! capture is triggered: the buffer keeps recording for the post-trigger time
+//! and is then frozen, so that it holds the waveform from before the trigger
+//! to after it. The host downloads the frozen waveform over I2C (registers
+//! 0x96 and 0x97) and re-arms the capture.
+
+use defmt::info;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::config::{
+    IIN_MAX_VALUE, VIN_MAX_VALUE, VSCAP_MAX_VALUE, WAVEFORM_POST_TRIGGER_SAMPLES,
+    WAVEFORM_PRE_TRIGGER_SAMPLES, WAVEFORM_SAMPLE_INTERVAL_US,
+};
+
+const WAVEFORM_SAMPLES: usize = WAVEFORM_PRE_TRIGGER_SAMPLES + WAVEFORM_POST_TRIGGER_SAMPLES;
+/// Size of a sample in the I2C wire format: VIN, VSCAP, IIN (u16 BE each)
+const SAMPLE_SIZE: usize = 6;
+/// Samples per I2C download chunk
+const CHUNK_SAMPLES: usize = 16;
+
+/// Size of a download chunk in the I2C wire format
+pub const WAVEFORM_CHUNK_SIZE: usize = CHUNK_SAMPLES * SAMPLE_SIZE;
+/// Size of the capture status in the I2C wire format
+pub const WAVEFORM_STATUS_SIZE: usize = 11;
+
+/// Capture states. The discriminant is part of the I2C API.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+#[repr(u8)]
+enum CaptureState {
+    /// Recording continuously, waiting for a trigger
+    Armed = 0,
+    /// Triggered, recording the post-trigger samples
+    Capturing = 1,
+    /// The capture is complete and held for download
+    Frozen = 2,
+}
+
+struct Waveform {
+    /// VIN, VSCAP and IIN, scaled as in registers 0x20-0x22
+    samples: [[u16; 3]; WAVEFORM_SAMPLES],
+    /// Index of the next sample to be written, and of the oldest sample
+    next: usize,
+    state: CaptureState,
+    /// Samples left to record after the trigger
+    remaining: usize,
+    /// Uptime of the trigger in milliseconds
+    trigger_time_ms: u32,
+}
+
+static WAVEFORM: Mutex<CriticalSectionRawMutex, Waveform> = Mutex::new(Waveform {
+    samples: [[0; 3]; WAVEFORM_SAMPLES],
+    next: 0,
+    state: CaptureState::Armed,
+    remaining: 0,
+    trigger_time_ms: 0,
+});
+
+/// Record a sample of the calibrated VIN, VSCAP and IIN readings
+pub async fn record_waveform_sample(vin: f32, vscap: f32, iin: f32) {
+    let sample = [
+        (65535.0 * vin / VIN_MAX_VALUE) as u16,
+        (65535.0 * vscap / VSCAP_MAX_VALUE) as u16,
+        (65535.0 * iin / IIN_MAX_VALUE) as u16,
+    ];
+    let mut waveform = WAVEFORM.lock().await;
+    if waveform.state == CaptureState::Frozen {
+        return;
+    }
+    let next = waveform.next;
+    waveform.samples[next] = sample;
+    waveform.next = (next + 1) % WAVEFORM_SAMPLES;
+    if waveform.state == CaptureState::Capturing {
+        waveform.remaining -= 1;
+        if waveform.remaining == 0 {
+            waveform.state = CaptureState::Frozen;
+            info!("Waveform capture complete");
+        }
+    }
+}
+
+/// Trigger a capture if one is armed
+pub async fn trigger_waveform_capture() {
+    let mut waveform = WAVEFORM.lock().await;
+    if waveform.state != CaptureState::Armed {
+        return;
+    }
+    info!("Waveform capture triggered");
+    waveform.state = CaptureState::Capturing;
+    waveform.remaining = WAVEFORM_POST_TRIGGER_SAMPLES;
+    waveform.trigger_time_ms = Instant::now().as_millis() as u32;
+}
+
+/// Discard the frozen capture and wait for the next trigger
+pub async fn rearm_waveform_capture() {
+    let mut waveform = WAVEFORM.lock().await;
+    waveform.samples.fill([0; 3]);
+    waveform.next = 0;
+    waveform.state = CaptureState::Armed;
+    waveform.remaining = 0;
+    waveform.trigger_time_ms = 0;
+}
+
+/// Capture status in I2C wire format: state, sample interval in microseconds (u16 BE),
+/// trigger sample index (u16 BE), sample count (u16 BE), trigger uptime in milliseconds
+/// (u32 BE)
+pub async fn get_waveform_status() -> [u8; WAVEFORM_STATUS_SIZE] {
+    let waveform = WAVEFORM.lock().await;
+    let mut bytes = [0u8; WAVEFORM_STATUS_SIZE];
+    bytes[0] = waveform.state as u8;
+    bytes[1..3].copy_from_slice(&(WAVEFORM_SAMPLE_INTERVAL_US as u16).to_be_bytes());
+    bytes[3..5].copy_from_slice(&(WAVEFORM_PRE_TRIGGER_SAMPLES as u16).to_be_bytes());
+    bytes[5..7].copy_from_slice(&(WAVEFORM_SAMPLES as u16).to_be_bytes());
+    bytes[7..11].copy_from_slice(&waveform.trigger_time_ms.to_be_bytes());
+    bytes
+}
+
+/// Read a chunk of the frozen waveform starting at sample `start` (0 = oldest) in I2C
+/// wire format: VIN, VSCAP and IIN (u16 BE each) per sample. Samples beyond the end, and
+/// all samples while the capture is not frozen, read as zeros.
+pub async fn read_waveform_chunk(start: u16) -> [u8; WAVEFORM_CHUNK_SIZE] {
+    let mut bytes = [0u8; WAVEFORM_CHUNK_SIZE];
+    let waveform = WAVEFORM.lock().await;
+    if waveform.state != CaptureState::Frozen {
+        return bytes;
+    }
+    let start = start as usize;
+    for i in 0..CHUNK_SAMPLES.min(WAVEFORM_SAMPLES.saturating_sub(start)) {
+        let sample = waveform.samples[(waveform.next + start + i) % WAVEFORM_SAMPLES];
+        for (channel, value) in sample.iter().enumerate() {
+            let offset = i * SAMPLE_SIZE + channel * 2;
+            bytes[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+        }
+    }
+    bytes
+}